@@ -0,0 +1,111 @@
+use abi_stable::std_types::Tuple2;
+use nadi_core::attrs::{AttrMap, Attribute, FromAttribute};
+use nadi_core::prelude::*;
+
+/// network attribute with the figure specifications
+const FIGURES_ATTR: &str = "figures";
+
+/// Named figure defined in the tasks file like
+/// `network.figures = {overview = {layout = "network", filter = "ORDER > 1"}}`,
+/// the other keys overwrite the network attributes used for drawing
+/// (`drawtable`, `bg_color`, etc.) while the figure is shown
+#[derive(Debug, Clone)]
+pub struct Figure {
+    pub name: String,
+    /// propositional expression for the nodes to show
+    pub filter: Option<String>,
+    /// draw only the network even if there is a `drawtable`
+    pub network_only: bool,
+    attrs: AttrMap,
+}
+
+impl Figure {
+    fn from_map(name: &str, map: &AttrMap) -> Self {
+        let mut attrs = map.clone();
+        let filter = attrs
+            .remove("filter")
+            .into_option()
+            .and_then(|f| String::from_attr(&f))
+            .filter(|f| !f.trim().is_empty());
+        let layout = attrs
+            .remove("layout")
+            .into_option()
+            .and_then(|l| String::from_attr(&l));
+        let network_only = match layout.as_deref() {
+            Some("network") => true,
+            Some("table") | None => false,
+            Some(l) => {
+                eprintln!("Invalid layout {l:?} for figure {name}, should be network or table");
+                false
+            }
+        };
+        Self {
+            name: name.to_string(),
+            filter,
+            network_only,
+            attrs,
+        }
+    }
+}
+
+/// Figures defined in the network, sorted by their names
+pub fn figures(net: &Network) -> Vec<Figure> {
+    let Some(Attribute::Table(figs)) = net.attr(FIGURES_ATTR) else {
+        return vec![];
+    };
+    let mut figs: Vec<Figure> = figs
+        .iter()
+        .filter_map(|Tuple2(name, spec)| match spec {
+            Attribute::Table(map) => Some(Figure::from_map(name, map)),
+            _ => {
+                eprintln!("Figure {name} should be a table of attributes");
+                None
+            }
+        })
+        .collect();
+    figs.sort_by(|a, b| a.name.cmp(&b.name));
+    figs
+}
+
+pub fn figure(net: &Network, name: &str) -> Option<Figure> {
+    figures(net).into_iter().find(|f| f.name == name)
+}
+
+/// Network attributes used for drawing, the ones in the figure take
+/// precedence over the network's own
+#[derive(Clone, Copy)]
+pub struct DrawAttrs<'a> {
+    net: &'a Network,
+    figure: Option<&'a Figure>,
+}
+
+impl<'a> DrawAttrs<'a> {
+    pub fn attr(&self, name: &str) -> Option<&'a Attribute> {
+        if let Some(fig) = self.figure {
+            if fig.network_only && name == "drawtable" {
+                return None;
+            }
+            if let Some(v) = fig.attrs.get(name) {
+                return Some(v);
+            }
+        }
+        self.net.attr(name)
+    }
+
+    pub fn try_attr<T: FromAttribute>(&self, name: &str) -> Result<T, String> {
+        match self.attr(name) {
+            Some(v) => T::try_from_attr(v),
+            None => Err(format!("Attribute {name} not found")),
+        }
+    }
+}
+
+/// Run `func` with the drawing attributes of the figure, the network
+/// itself is not changed
+pub fn with_figure<T>(net: &Network, name: Option<&str>, func: impl FnOnce(&DrawAttrs) -> T) -> T {
+    let fig = name.and_then(|n| figure(net, n));
+    func(&DrawAttrs {
+        net,
+        figure: fig.as_ref(),
+    })
+}
@@ -0,0 +1,394 @@
+use nadi_core::attrs::Attribute;
+use nadi_core::parser::tokenizer::{self, TaskToken, Token};
+use nadi_core::prelude::*;
+use regex::Regex;
+
+/// Value in the filter, an attribute of the node or a literal
+#[derive(Debug, Clone)]
+enum Term {
+    Attr(String),
+    Lit(Attribute),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum CmpOp {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Term(Term),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+    Compare(Term, CmpOp, Term),
+    Match(Term, Regex),
+}
+
+/// Propositional expression to select the nodes like
+/// `inputs_sum > drain_a & !dam`; it is evaluated on the attributes of
+/// each node without running any task, so the network is never changed
+#[derive(Debug, Clone)]
+pub struct Filter(Expr);
+
+impl Filter {
+    /// Parse the text, anything other than a single expression (tasks,
+    /// assignments, function calls) is an error
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let tokens = tokenizer::get_tokens(text).map_err(|e| e.ty.message().to_string())?;
+        let ops = operators(&tokens);
+        let mut parser = Parser { ops: &ops, pos: 0 };
+        let expr = parser.or()?;
+        match parser.peek() {
+            None => Ok(Self(expr)),
+            Some((_, op)) => Err(format!("Unexpected {} after the expression", op.content())),
+        }
+    }
+
+    pub fn eval(&self, lookup: &dyn Fn(&str) -> Option<Attribute>) -> Result<bool, String> {
+        self.0.eval(lookup)
+    }
+
+    /// Evaluate with the attributes of the node, and the special
+    /// variables `NAME`, `INDEX`, `LEVEL` and `ORDER`
+    pub fn matches(&self, node: &NodeInner) -> Result<bool, String> {
+        self.eval(&|name| match name {
+            "NAME" => Some(Attribute::String(node.name().into())),
+            "INDEX" => Some(Attribute::Integer(node.index() as i64)),
+            "LEVEL" => Some(Attribute::Integer(node.level() as i64)),
+            "ORDER" => Some(Attribute::Integer(node.order() as i64)),
+            _ => node.attr(name).cloned(),
+        })
+    }
+}
+
+/// Indices of the nodes of the network passing the filter
+pub fn filter_nodes(net: &Network, filter: &str) -> Result<Vec<usize>, String> {
+    if filter.trim().is_empty() {
+        return Ok(net.nodes().map(|n| n.lock().index()).collect());
+    }
+    let filter = Filter::parse(filter)?;
+    let mut nodes = vec![];
+    for n in net.nodes() {
+        let n = n.lock();
+        if filter.matches(&n)? {
+            nodes.push(n.index());
+        }
+    }
+    Ok(nodes)
+}
+
+impl Expr {
+    fn eval(&self, lookup: &dyn Fn(&str) -> Option<Attribute>) -> Result<bool, String> {
+        Ok(match self {
+            Self::Term(t) => t.value(lookup).map(|v| truthy(&v)).unwrap_or(false),
+            Self::Not(f) => !f.eval(lookup)?,
+            Self::And(a, b) => a.eval(lookup)? && b.eval(lookup)?,
+            Self::Or(a, b) => a.eval(lookup)? || b.eval(lookup)?,
+            Self::Compare(a, op, b) => match (a.value(lookup), b.value(lookup)) {
+                (Some(a), Some(b)) => compare(&a, *op, &b)?,
+                // nodes without the attribute don't pass
+                _ => false,
+            },
+            Self::Match(t, re) => match t.value(lookup) {
+                Some(Attribute::String(s)) => re.is_match(&s),
+                Some(_) => return Err("Only strings can be matched with a regex".to_string()),
+                None => false,
+            },
+        })
+    }
+}
+
+impl Term {
+    fn value(&self, lookup: &dyn Fn(&str) -> Option<Attribute>) -> Option<Attribute> {
+        match self {
+            Self::Attr(name) => lookup(name),
+            Self::Lit(v) => Some(v.clone()),
+        }
+    }
+}
+
+fn truthy(v: &Attribute) -> bool {
+    match v {
+        Attribute::Bool(b) => *b,
+        Attribute::Integer(i) => *i != 0,
+        Attribute::Float(f) => *f != 0.0,
+        Attribute::String(s) => !s.is_empty(),
+        _ => true,
+    }
+}
+
+fn compare(a: &Attribute, op: CmpOp, b: &Attribute) -> Result<bool, String> {
+    let ord = match (a, b) {
+        (Attribute::String(a), Attribute::String(b)) => a.as_str().cmp(b.as_str()),
+        (Attribute::Bool(a), Attribute::Bool(b)) => a.cmp(b),
+        _ => match (number(a), number(b)) {
+            (Some(a), Some(b)) => a
+                .partial_cmp(&b)
+                .ok_or("Can't compare NaN values".to_string())?,
+            _ => return Err("Only numbers, strings or booleans can be compared".to_string()),
+        },
+    };
+    Ok(match op {
+        CmpOp::Eq => ord.is_eq(),
+        CmpOp::Ne => ord.is_ne(),
+        CmpOp::Lt => ord.is_lt(),
+        CmpOp::Le => ord.is_le(),
+        CmpOp::Gt => ord.is_gt(),
+        CmpOp::Ge => ord.is_ge(),
+    })
+}
+
+fn number(v: &Attribute) -> Option<f64> {
+    match v {
+        Attribute::Integer(i) => Some(*i as f64),
+        Attribute::Float(f) => Some(*f),
+        _ => None,
+    }
+}
+
+/// Token of the expression, the operators split by the tokenizer
+/// (like `>` `=`) are joined
+#[derive(Clone, Copy)]
+enum Op<'a> {
+    Sym(&'a str),
+    Joined(&'a str),
+    Value(&'a Token<'a>),
+}
+
+impl Op<'_> {
+    fn content(&self) -> &str {
+        match self {
+            Self::Sym(s) | Self::Joined(s) => s,
+            Self::Value(t) => t.content,
+        }
+    }
+}
+
+type PosOp<'a> = (usize, Op<'a>);
+
+fn operators<'a>(tokens: &'a [Token<'a>]) -> Vec<PosOp<'a>> {
+    const JOINED: [&str; 6] = [">=", "<=", "==", "!=", "&&", "||"];
+    let mut ops: Vec<PosOp> = vec![];
+    for (i, t) in tokens.iter().enumerate() {
+        match t.ty {
+            TaskToken::WhiteSpace => continue,
+            TaskToken::Variable
+            | TaskToken::Bool
+            | TaskToken::Integer
+            | TaskToken::Float
+            | TaskToken::String(_)
+            | TaskToken::Keyword(_) => ops.push((i, Op::Value(t))),
+            _ => {
+                let joined = match ops.last() {
+                    Some(&(j, Op::Sym(prev))) if j + 1 == i => JOINED
+                        .into_iter()
+                        .find(|o| *o == format!("{prev}{}", t.content)),
+                    _ => None,
+                };
+                match joined {
+                    Some(j) => {
+                        ops.pop();
+                        ops.push((i, Op::Joined(j)));
+                    }
+                    None => ops.push((i, Op::Sym(t.content))),
+                }
+            }
+        }
+    }
+    ops
+}
+
+struct Parser<'a, 'b> {
+    ops: &'b [PosOp<'a>],
+    pos: usize,
+}
+
+impl<'a> Parser<'a, '_> {
+    fn peek(&self) -> Option<PosOp<'a>> {
+        self.ops.get(self.pos).copied()
+    }
+
+    fn symbol(&self) -> Option<&'a str> {
+        match self.peek()?.1 {
+            Op::Sym(s) | Op::Joined(s) => Some(s),
+            Op::Value(t) if matches!(t.ty, TaskToken::Keyword(_)) || t.content == "match" => {
+                Some(t.content)
+            }
+            Op::Value(_) => None,
+        }
+    }
+
+    fn or(&mut self) -> Result<Expr, String> {
+        let mut left = self.and()?;
+        while matches!(self.symbol(), Some("|" | "||")) {
+            self.pos += 1;
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, String> {
+        let mut left = self.not()?;
+        while matches!(self.symbol(), Some("&" | "&&")) {
+            self.pos += 1;
+            left = Expr::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, String> {
+        if self.symbol() == Some("!") {
+            self.pos += 1;
+            return Ok(Expr::Not(Box::new(self.not()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Expr, String> {
+        if self.symbol() == Some("(") {
+            self.pos += 1;
+            let inner = self.or()?;
+            if self.symbol() != Some(")") {
+                return Err("Unclosed parenthesis in the expression".to_string());
+            }
+            self.pos += 1;
+            return Ok(inner);
+        }
+        let left = self.term()?;
+        let op = match self.symbol() {
+            Some("==") => CmpOp::Eq,
+            Some("!=") => CmpOp::Ne,
+            Some("<") => CmpOp::Lt,
+            Some("<=") => CmpOp::Le,
+            Some(">") => CmpOp::Gt,
+            Some(">=") => CmpOp::Ge,
+            Some("match") => {
+                self.pos += 1;
+                let Term::Lit(Attribute::String(re)) = self.term()? else {
+                    return Err("match needs a string with the regex".to_string());
+                };
+                let re = Regex::new(&re).map_err(|e| e.to_string())?;
+                return Ok(Expr::Match(left, re));
+            }
+            _ => return Ok(Expr::Term(left)),
+        };
+        self.pos += 1;
+        Ok(Expr::Compare(left, op, self.term()?))
+    }
+
+    fn term(&mut self) -> Result<Term, String> {
+        let Some((_, op)) = self.peek() else {
+            return Err("Expression ended before a value".to_string());
+        };
+        let Op::Value(t) = op else {
+            return Err(format!("Expected a value, found {}", op.content()));
+        };
+        let term = match t.ty {
+            TaskToken::Variable => Term::Attr(t.content.to_string()),
+            TaskToken::Bool => Term::Lit(Attribute::Bool(t.content == "true")),
+            TaskToken::Integer => Term::Lit(Attribute::Integer(
+                t.content.parse().map_err(|_| "Invalid integer")?,
+            )),
+            TaskToken::Float => Term::Lit(Attribute::Float(
+                t.content.parse().map_err(|_| "Invalid float")?,
+            )),
+            TaskToken::String(_) => Term::Lit(Attribute::String(unquote(t.content).into())),
+            _ => return Err(format!("{} can't be used in the expression", t.content)),
+        };
+        self.pos += 1;
+        Ok(term)
+    }
+}
+
+/// Contents of the string literal without the quotes and escapes
+fn unquote(s: &str) -> String {
+    let inner = s
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .unwrap_or(s);
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => out.push('\n'),
+            Some('t') => out.push('\t'),
+            Some(e) => out.push(e),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn eval(filter: &str, attrs: &[(&str, Attribute)]) -> Result<bool, String> {
+        let attrs: HashMap<&str, Attribute> = attrs.iter().cloned().collect();
+        Filter::parse(filter)?.eval(&|name| attrs.get(name).cloned())
+    }
+
+    #[test]
+    fn comparisons() {
+        let attrs = [
+            ("inputs_sum", Attribute::Float(10.0)),
+            ("drain_a", Attribute::Float(12.5)),
+            ("ndam", Attribute::Integer(0)),
+        ];
+        assert_eq!(eval("inputs_sum > drain_a", &attrs), Ok(false));
+        assert_eq!(eval("inputs_sum <= drain_a & drain_a", &attrs), Ok(true));
+        assert_eq!(eval("ndam == 0 | inputs_sum > 100", &attrs), Ok(true));
+        assert_eq!(eval("!(ndam != 0)", &attrs), Ok(true));
+        assert_eq!(eval("ndam", &attrs), Ok(false));
+    }
+
+    #[test]
+    fn missing_attributes_fail() {
+        assert_eq!(eval("missing", &[]), Ok(false));
+        assert_eq!(eval("missing > 1", &[]), Ok(false));
+        assert_eq!(eval("!missing", &[]), Ok(true));
+    }
+
+    #[test]
+    fn strings_and_regex() {
+        let attrs = [("NAME", Attribute::String("smithland".into()))];
+        assert_eq!(eval("NAME == \"smithland\"", &attrs), Ok(true));
+        assert_eq!(eval("NAME match \"^smith\"", &attrs), Ok(true));
+        assert_eq!(eval("NAME match \"^racine\"", &attrs), Ok(false));
+        assert!(eval("NAME > 1", &attrs).is_err());
+    }
+
+    #[test]
+    fn rejects_anything_but_an_expression() {
+        for filter in [
+            "x) ; node.y = 1 ; node(z",
+            "x = 1",
+            "count()",
+            "(x > 1",
+            "x > 1 y",
+            "node.x",
+        ] {
+            assert!(
+                Filter::parse(filter).is_err(),
+                "{filter} should be rejected"
+            );
+        }
+    }
+
+    #[test]
+    fn unquote_escapes() {
+        assert_eq!(unquote("\"a\\\"b\""), "a\"b");
+        assert_eq!(unquote("\"{_NAME}.csv\""), "{_NAME}.csv");
+    }
+}
@@ -0,0 +1,237 @@
+use abi_stable::sabi_trait::prelude::TD_Opaque;
+use abi_stable::std_types::{RString, RVec};
+use nadi_core::functions::{
+    FuncArg, FuncArgType, FunctionCtx, FunctionRet, NadiFunctions, NetworkFunction,
+    NetworkFunction_TO,
+};
+use nadi_core::prelude::*;
+use std::sync::{Arc, Mutex};
+
+/// prefix of the functions provided by the gui
+const PREFIX: &str = "gui";
+
+/// Actions requested by the gui functions from the tasks, the window
+/// does them after the task has run
+#[derive(Debug, Clone)]
+pub enum GuiRequest {
+    Scatter {
+        x: String,
+        y: String,
+        filter: String,
+    },
+    Histogram {
+        attr: String,
+        filter: String,
+    },
+    Series {
+        series: String,
+        x: Option<String>,
+        filter: String,
+    },
+    ShowTable,
+    Export {
+        file: String,
+        plot: bool,
+    },
+}
+
+/// Requests of the gui functions registered in one task context
+#[derive(Debug, Clone, Default)]
+pub struct GuiRequests(Arc<Mutex<Vec<GuiRequest>>>);
+
+impl GuiRequests {
+    /// Requests made since the last call
+    pub fn take(&self) -> Vec<GuiRequest> {
+        self.0
+            .lock()
+            .map(|mut r| std::mem::take(&mut *r))
+            .unwrap_or_default()
+    }
+
+    fn push(&self, req: GuiRequest) -> FunctionRet {
+        match self.0.lock() {
+            Ok(mut r) => {
+                r.push(req);
+                FunctionRet::None
+            }
+            Err(e) => FunctionRet::Error(e.to_string().into()),
+        }
+    }
+}
+
+/// Register the gui functions into the task context functions, the
+/// requests they make are queued in the returned [`GuiRequests`]
+pub fn register(functions: &mut NadiFunctions) -> GuiRequests {
+    let requests = GuiRequests::default();
+    let kinds = [
+        GuiKind::Scatter,
+        GuiKind::Histogram,
+        GuiKind::Series,
+        GuiKind::ShowTable,
+        GuiKind::Export,
+    ];
+    for kind in kinds {
+        let f = GuiFunction {
+            kind,
+            requests: requests.clone(),
+        };
+        functions.register_network_function(PREFIX, NetworkFunction_TO::from_value(f, TD_Opaque));
+    }
+    requests
+}
+
+#[derive(Debug, Clone)]
+struct GuiFunction {
+    kind: GuiKind,
+    requests: GuiRequests,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum GuiKind {
+    Scatter,
+    Histogram,
+    Series,
+    ShowTable,
+    Export,
+}
+
+fn arg(name: &str, ty: &str, help: &str, category: FuncArgType) -> FuncArg {
+    FuncArg {
+        name: name.into(),
+        ty: ty.into(),
+        help: help.into(),
+        category,
+    }
+}
+
+/// String argument of the function, or the default if it is optional
+fn str_arg(
+    ctx: &FunctionCtx,
+    ind: usize,
+    name: &str,
+    default: Option<&str>,
+) -> Result<String, String> {
+    match ctx.arg_kwarg::<String>(ind, name) {
+        Some(r) => r,
+        None => default
+            .map(String::from)
+            .ok_or_else(|| format!("Argument {ind} ({name} [String]) is required")),
+    }
+}
+
+impl GuiKind {
+    fn call_inner(&self, ctx: &FunctionCtx) -> Result<GuiRequest, String> {
+        Ok(match self {
+            Self::Scatter => GuiRequest::Scatter {
+                x: str_arg(ctx, 0, "x", None)?,
+                y: str_arg(ctx, 1, "y", None)?,
+                filter: str_arg(ctx, 2, "filter", Some(""))?,
+            },
+            Self::Histogram => GuiRequest::Histogram {
+                attr: str_arg(ctx, 0, "attr", None)?,
+                filter: str_arg(ctx, 1, "filter", Some(""))?,
+            },
+            Self::Series => GuiRequest::Series {
+                series: str_arg(ctx, 0, "series", None)?,
+                x: Some(str_arg(ctx, 1, "x", Some(""))?).filter(|x| !x.is_empty()),
+                filter: str_arg(ctx, 2, "filter", Some(""))?,
+            },
+            Self::ShowTable => GuiRequest::ShowTable,
+            Self::Export => GuiRequest::Export {
+                file: str_arg(ctx, 0, "file", None)?,
+                plot: match str_arg(ctx, 1, "figure", Some("network"))?.as_str() {
+                    "network" => false,
+                    "plot" => true,
+                    f => return Err(format!("Invalid figure {f:?}, should be network or plot")),
+                },
+            },
+        })
+    }
+}
+
+impl NetworkFunction for GuiFunction {
+    fn name(&self) -> RString {
+        match self.kind {
+            GuiKind::Scatter => "gui_plot_scatter",
+            GuiKind::Histogram => "gui_plot_histogram",
+            GuiKind::Series => "gui_plot_series",
+            GuiKind::ShowTable => "gui_show_table",
+            GuiKind::Export => "gui_export",
+        }
+        .into()
+    }
+
+    fn help(&self) -> RString {
+        match self.kind {
+            GuiKind::Scatter => "Scatter plot of two node attributes in the plot panel\n\nOnly the nodes satisfying the propositional `filter` are plotted if it is given.",
+            GuiKind::Histogram => "Histogram of a node attribute in the plot panel\n\nOnly the nodes satisfying the propositional `filter` are used if it is given.",
+            GuiKind::Series => "Plot the series of the nodes in the plot panel\n\nThe values are plotted against the series `x` if it is given, or their position otherwise. Only the nodes satisfying the propositional `filter` are plotted if it is given.",
+            GuiKind::ShowTable => "Show the table view of the network",
+            GuiKind::Export => "Export the network diagram or the plot to a file\n\nThe format is based on the extension of the file (svg, pdf or png).",
+        }
+        .into()
+    }
+
+    fn short_help(&self) -> RString {
+        self.help().split('\n').next().unwrap_or_default().into()
+    }
+
+    fn code(&self) -> RString {
+        "".into()
+    }
+
+    fn args(&self) -> RVec<FuncArg> {
+        let filter = || {
+            arg(
+                "filter",
+                "String",
+                "Propositional expression for the nodes",
+                FuncArgType::DefArg("\"\"".into()),
+            )
+        };
+        match self.kind {
+            GuiKind::Scatter => vec![
+                arg("x", "String", "Attribute for the x-axis", FuncArgType::Arg),
+                arg("y", "String", "Attribute for the y-axis", FuncArgType::Arg),
+                filter(),
+            ],
+            GuiKind::Histogram => vec![
+                arg(
+                    "attr",
+                    "String",
+                    "Attribute for the histogram",
+                    FuncArgType::Arg,
+                ),
+                filter(),
+            ],
+            GuiKind::Series => vec![
+                arg("series", "String", "Series to plot", FuncArgType::Arg),
+                arg(
+                    "x",
+                    "String",
+                    "Series for the x-axis",
+                    FuncArgType::DefArg("\"\"".into()),
+                ),
+                filter(),
+            ],
+            GuiKind::ShowTable => vec![],
+            GuiKind::Export => vec![
+                arg("file", "String", "Output file", FuncArgType::Arg),
+                arg(
+                    "figure",
+                    "String",
+                    "Figure to export (network or plot)",
+                    FuncArgType::DefArg("\"network\"".into()),
+                ),
+            ],
+        }
+        .into()
+    }
+
+    fn call(&self, _net: &mut Network, ctx: &FunctionCtx) -> FunctionRet {
+        match self.kind.call_inner(ctx) {
+            Ok(req) => self.requests.push(req),
+            Err(e) => FunctionRet::Error(e.into()),
+        }
+    }
+}
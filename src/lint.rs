@@ -0,0 +1,591 @@
+use nadi_core::attrs::HasAttributes;
+use nadi_core::functions::{FuncArg, FuncArgType, NadiFunctions};
+use nadi_core::network::Network;
+use nadi_core::parser::tokenizer::{TaskToken, Token};
+use nadi_core::tasks::TaskKeyword;
+use std::collections::{HashMap, HashSet};
+
+/// Problem found in the tasks without running them
+#[derive(Debug, Clone)]
+pub struct LintWarning {
+    /// character offset of the problem from the start of the tokens
+    pub offset: usize,
+    /// length of the problem in characters
+    pub len: usize,
+    pub message: String,
+}
+
+/// Attributes set by the tasks of a file, with the index of the first
+/// task setting them
+#[derive(Debug, Clone, Default)]
+pub struct Assigned {
+    node: HashMap<String, usize>,
+    network: HashMap<String, usize>,
+    /// character offsets of the starts of the tasks
+    tasks: Vec<usize>,
+}
+
+impl Assigned {
+    /// Attributes set by the tasks in the tokens of the whole file
+    pub fn new(tokens: &[Token]) -> Self {
+        let mut assigned = Self {
+            tasks: task_starts(tokens),
+            ..Default::default()
+        };
+        for r in attr_refs(tokens).iter().filter(|r| r.set) {
+            let ind = assigned.task_index(r.task);
+            let attrs = match r.scope {
+                Scope::Node => &mut assigned.node,
+                Scope::Network => &mut assigned.network,
+                Scope::Env => continue,
+            };
+            attrs.entry(r.name.to_string()).or_insert(ind);
+        }
+        assigned
+    }
+
+    /// Whether the same attributes are set by the same tasks, even if
+    /// the tasks moved in the file
+    pub fn same_tasks(&self, other: &Self) -> bool {
+        self.node == other.node && self.network == other.network
+    }
+
+    fn task_index(&self, offset: usize) -> usize {
+        self.tasks.partition_point(|&t| t < offset)
+    }
+
+    /// Whether a task before the one at the offset sets the attribute
+    fn set_before(&self, scope: Scope, name: &str, task: usize) -> bool {
+        let attrs = match scope {
+            Scope::Node => &self.node,
+            Scope::Network => &self.network,
+            Scope::Env => return true,
+        };
+        attrs.get(name).is_some_and(|&i| i < self.task_index(task))
+    }
+}
+
+/// Attribute in the tasks; the node names in the selections and the
+/// names of the keyword arguments are not attributes
+#[derive(Debug, Clone)]
+pub struct AttrRef<'a> {
+    /// character offset of the name from the start of the tokens
+    pub offset: usize,
+    pub name: &'a str,
+    scope: Scope,
+    /// character offset of the start of the task
+    task: usize,
+    /// whether the task sets the attribute instead of reading it
+    set: bool,
+}
+
+/// Keyword of the task the tokens belong to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Scope {
+    Node,
+    Network,
+    Env,
+}
+
+impl Scope {
+    fn from_keyword(kw: &str) -> Self {
+        match kw {
+            "node" => Self::Node,
+            "network" | "net" => Self::Network,
+            _ => Self::Env,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Self::Node => "node",
+            Self::Network => "network",
+            Self::Env => "env",
+        }
+    }
+}
+
+/// Variables with a special meaning in the tasks, they are not attributes
+const SPECIAL_VARS: [&str; 4] = ["NAME", "INDEX", "LEVEL", "ORDER"];
+
+/// Token with its character offset
+type PosToken<'a, 'b> = (usize, &'b Token<'a>);
+
+/// Check the tokens of the parsed tasks for unknown functions, wrong
+/// arguments and the attributes read before any task sets them; the
+/// tokens start at the character offset `base` of the file the
+/// `assigned` attributes are from
+pub fn lint(
+    tokens: &[Token],
+    funcs: &NadiFunctions,
+    net: &Network,
+    assigned: &Assigned,
+    base: usize,
+) -> Vec<LintWarning> {
+    let toks = pos_tokens(tokens);
+    let node_attrs: HashSet<String> = net
+        .nodes()
+        .flat_map(|n| {
+            n.lock()
+                .attr_map()
+                .keys()
+                .map(|k| k.to_string())
+                .collect::<Vec<_>>()
+        })
+        .collect();
+    let net_attrs: HashSet<String> = net.attr_map().keys().map(|k| k.to_string()).collect();
+
+    let mut warnings = vec![];
+    let mut scope = Scope::Env;
+    let mut line_start = true;
+    for (i, &(off, t)) in toks.iter().enumerate() {
+        match &t.ty {
+            TaskToken::NewLine => {
+                line_start = true;
+                continue;
+            }
+            TaskToken::Keyword(k) if line_start => {
+                if !matches!(k, TaskKeyword::In | TaskKeyword::Match) {
+                    scope = Scope::from_keyword(t.content);
+                }
+            }
+            TaskToken::Function => match function_args(funcs, scope, t.content) {
+                Some(args) => {
+                    let call = call_args(&toks[i + 1..]);
+                    warnings.extend(check_args(t.content, &args, &call, off));
+                }
+                None => warnings.push(LintWarning {
+                    offset: off,
+                    len: t.content.chars().count(),
+                    message: format!(
+                        "Function {} not found for {} tasks",
+                        t.content,
+                        scope.name()
+                    ),
+                }),
+            },
+            _ => (),
+        }
+        line_start = false;
+    }
+    for r in attr_refs(tokens).into_iter().filter(|r| !r.set) {
+        let existing = match r.scope {
+            Scope::Node => &node_attrs,
+            Scope::Network => &net_attrs,
+            Scope::Env => continue,
+        };
+        if !existing.contains(r.name) && !assigned.set_before(r.scope, r.name, base + r.task) {
+            warnings.push(LintWarning {
+                offset: r.offset,
+                len: r.name.chars().count(),
+                message: format!(
+                    "{} attribute {} is not in the network or set by an earlier task",
+                    r.scope.name(),
+                    r.name
+                ),
+            });
+        }
+    }
+    warnings.sort_by_key(|w| w.offset);
+    warnings
+}
+
+/// Tokens with their character offsets, without the whitespace and
+/// the comments
+fn pos_tokens<'a, 'b>(tokens: &'b [Token<'a>]) -> Vec<PosToken<'a, 'b>> {
+    let mut offset = 0;
+    tokens
+        .iter()
+        .map(|t| {
+            let o = offset;
+            offset += t.content.chars().count();
+            (o, t)
+        })
+        .filter(|(_, t)| !matches!(t.ty, TaskToken::WhiteSpace | TaskToken::Comment))
+        .collect()
+}
+
+/// Character offsets of the starts of the tasks
+fn task_starts(tokens: &[Token]) -> Vec<usize> {
+    let mut starts = vec![];
+    let mut line_start = true;
+    for (off, t) in pos_tokens(tokens) {
+        match t.ty {
+            TaskToken::NewLine => {
+                line_start = true;
+                continue;
+            }
+            TaskToken::Keyword(TaskKeyword::In | TaskKeyword::Match) => (),
+            TaskToken::Keyword(_) if line_start => starts.push(off),
+            _ => (),
+        }
+        line_start = false;
+    }
+    starts
+}
+
+/// Attributes set and read by the tasks in the tokens
+pub fn attr_refs<'a>(tokens: &[Token<'a>]) -> Vec<AttrRef<'a>> {
+    let toks = pos_tokens(tokens);
+    let mut refs = vec![];
+    let mut scope = Scope::Env;
+    let mut task = 0;
+    // index of the keyword of the task in the tokens
+    let mut keyword = None;
+    let mut assigned = false;
+    let mut depth = 0;
+    // inside the node selection like `node[a -> b]` or `node<inputsfirst>`
+    let mut selection = false;
+    // keyword argument names, they are not attributes
+    let mut kwargs = HashSet::new();
+    let mut line_start = true;
+    for (i, &(off, t)) in toks.iter().enumerate() {
+        match &t.ty {
+            TaskToken::NewLine => {
+                line_start = true;
+                continue;
+            }
+            TaskToken::Keyword(k) if line_start => {
+                if !matches!(k, TaskKeyword::In | TaskKeyword::Match) {
+                    scope = Scope::from_keyword(t.content);
+                    task = off;
+                    keyword = Some(i);
+                    assigned = false;
+                    depth = 0;
+                }
+            }
+            TaskToken::Function => {
+                let call = call_args(&toks[i + 1..]);
+                kwargs.extend(call.iter().filter_map(|a| a.kwarg).map(|k| k.0));
+            }
+            TaskToken::Assignment if depth == 0 => assigned = true,
+            TaskToken::Variable
+                if !selection && !kwargs.contains(&off) && !SPECIAL_VARS.contains(&t.content) =>
+            {
+                // only the first part of `a.b` is the attribute, but
+                // `node.a` is the attribute `a`
+                let dotted = i > 1
+                    && toks[i - 1].1.content == "."
+                    && !matches!(toks[i - 2].1.ty, TaskToken::Keyword(_));
+                if !dotted {
+                    refs.push(AttrRef {
+                        offset: off,
+                        name: t.content,
+                        scope,
+                        task,
+                        set: !assigned && depth == 0,
+                    });
+                }
+            }
+            _ => (),
+        }
+        match t.content {
+            "[" | "<" if keyword.map(|k| k + 1) == Some(i) => selection = true,
+            "]" | ">" if selection => selection = false,
+            "(" => depth += 1,
+            ")" => depth -= 1,
+            _ => (),
+        }
+        line_start = false;
+    }
+    refs
+}
+
+fn function_args(funcs: &NadiFunctions, scope: Scope, name: &str) -> Option<Vec<FuncArg>> {
+    let env = || funcs.env(name).map(|f| f.args().into_iter().collect());
+    match scope {
+        Scope::Node => funcs
+            .node(name)
+            .map(|f| f.args().into_iter().collect())
+            .or_else(env),
+        Scope::Network => funcs
+            .network(name)
+            .map(|f| f.args().into_iter().collect())
+            .or_else(env),
+        Scope::Env => env(),
+    }
+}
+
+/// Argument in the function call
+struct CallArg<'a> {
+    /// offset and name of the keyword
+    kwarg: Option<(usize, &'a str)>,
+    offset: usize,
+    len: usize,
+    /// type of the value if it is a literal
+    literal: Option<&'static str>,
+}
+
+/// Arguments of the function call from the tokens after the name
+fn call_args<'a>(toks: &[PosToken<'a, '_>]) -> Vec<CallArg<'a>> {
+    if toks.first().map(|t| t.1.content) != Some("(") {
+        return vec![];
+    }
+    let mut args = vec![];
+    let mut current: Vec<PosToken> = vec![];
+    let mut depth = 0;
+    for &(off, t) in toks {
+        match t.content {
+            "(" | "[" | "{" => {
+                depth += 1;
+                if depth == 1 {
+                    continue;
+                }
+            }
+            ")" | "]" | "}" => {
+                depth -= 1;
+                if depth == 0 {
+                    args.extend(call_arg(&current));
+                    break;
+                }
+            }
+            "," if depth == 1 => {
+                args.extend(call_arg(&current));
+                current.clear();
+                continue;
+            }
+            _ => (),
+        }
+        if !matches!(t.ty, TaskToken::NewLine) {
+            current.push((off, t));
+        }
+    }
+    args
+}
+
+fn call_arg<'a>(toks: &[PosToken<'a, '_>]) -> Option<CallArg<'a>> {
+    let (kwarg, value) = match toks {
+        [(o, k), (_, eq), rest @ ..]
+            if matches!(k.ty, TaskToken::Variable) && matches!(eq.ty, TaskToken::Assignment) =>
+        {
+            (Some((*o, k.content)), rest)
+        }
+        _ => (None, toks),
+    };
+    let (offset, _) = toks.first()?;
+    let (last_off, last) = toks.last()?;
+    let literal = match value {
+        [(_, v)] => match v.ty {
+            TaskToken::String(_) => Some("String"),
+            TaskToken::Integer => Some("Integer"),
+            TaskToken::Float => Some("Float"),
+            TaskToken::Bool => Some("Bool"),
+            _ => None,
+        },
+        _ => None,
+    };
+    Some(CallArg {
+        kwarg,
+        offset: *offset,
+        len: last_off + last.content.chars().count() - offset,
+        literal,
+    })
+}
+
+fn check_args(name: &str, args: &[FuncArg], call: &[CallArg], offset: usize) -> Vec<LintWarning> {
+    let mut warnings = vec![];
+    let mut warn = |offset: usize, len: usize, message: String| {
+        warnings.push(LintWarning {
+            offset,
+            len,
+            message,
+        })
+    };
+    let variadic = args.iter().any(|a| matches!(a.category, FuncArgType::Args));
+    let any_kwarg = args
+        .iter()
+        .any(|a| matches!(a.category, FuncArgType::KwArgs));
+    let positional: Vec<&FuncArg> = args
+        .iter()
+        .filter(|a| {
+            matches!(
+                a.category,
+                FuncArgType::Arg | FuncArgType::OptArg | FuncArgType::DefArg(_)
+            )
+        })
+        .collect();
+    let mut given = HashSet::new();
+    let mut pos = 0;
+    for c in call {
+        let arg = match c.kwarg {
+            Some((koff, k)) => match args.iter().find(|a| a.name.as_str() == k) {
+                Some(a) => Some(a),
+                None => {
+                    if !any_kwarg {
+                        warn(
+                            koff,
+                            k.chars().count(),
+                            format!("Function {name} has no argument {k}"),
+                        );
+                    }
+                    None
+                }
+            },
+            None => {
+                pos += 1;
+                match positional.get(pos - 1) {
+                    Some(a) => Some(*a),
+                    None => {
+                        if !variadic {
+                            warn(
+                                c.offset,
+                                c.len,
+                                format!(
+                                    "Function {name} takes at most {} positional arguments",
+                                    positional.len()
+                                ),
+                            );
+                        }
+                        None
+                    }
+                }
+            }
+        };
+        if let Some(a) = arg {
+            given.insert(a.name.to_string());
+            if let Some(lit) = c.literal {
+                if !type_matches(&a.ty, lit) {
+                    warn(
+                        c.offset,
+                        c.len,
+                        format!("Argument {} should be {}, not {lit}", a.name, a.ty),
+                    );
+                }
+            }
+        }
+    }
+    for a in args {
+        if matches!(a.category, FuncArgType::Arg) && !given.contains(a.name.as_str()) {
+            warn(
+                offset,
+                name.chars().count(),
+                format!("Function {name} is missing the argument {}", a.name),
+            );
+        }
+    }
+    warnings
+}
+
+/// Whether the literal can be converted to the rust type of the argument,
+/// types that can't be decided from the name are accepted
+fn type_matches(ty: &str, literal: &str) -> bool {
+    let ty = ty.trim().trim_start_matches('&');
+    let ty = ty
+        .strip_prefix("Option<")
+        .and_then(|t| t.strip_suffix('>'))
+        .unwrap_or(ty)
+        .trim();
+    let int = ["i64", "u64", "i32", "u32", "usize", "isize", "u8", "i8"];
+    let float = ["f64", "f32"];
+    let string = ["String", "str", "RString", "PathBuf", "Path", "Template"];
+    match literal {
+        "String" => !(int.contains(&ty) || float.contains(&ty) || ty == "bool"),
+        "Integer" => !(string.contains(&ty) || ty == "bool"),
+        "Float" => !(int.contains(&ty) || string.contains(&ty) || ty == "bool"),
+        "Bool" => !(int.contains(&ty) || float.contains(&ty) || string.contains(&ty)),
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nadi_core::parser::tokenizer::get_tokens;
+    use nadi_core::tasks::TaskContext;
+
+    fn attr_warnings(code: &str) -> Vec<(usize, String)> {
+        let ctx = TaskContext::new(None);
+        let tokens = get_tokens(code).unwrap();
+        lint(
+            &tokens,
+            &ctx.functions,
+            &ctx.network,
+            &Assigned::new(&tokens),
+            0,
+        )
+        .into_iter()
+        .filter(|w| w.message.contains("attribute"))
+        .map(|w| (w.offset, w.message))
+        .collect()
+    }
+
+    #[test]
+    fn unknown_attribute() {
+        let warnings = attr_warnings("node.y = z\n");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].0, 9);
+        assert!(warnings[0].1.contains("node attribute z"));
+    }
+
+    #[test]
+    fn attribute_set_earlier() {
+        assert!(attr_warnings("node.x = 1\nnode.y = x\n").is_empty());
+    }
+
+    #[test]
+    fn attribute_set_later() {
+        let warnings = attr_warnings("node.y = x\nnode.x = 1\n");
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].0, 9);
+        assert_eq!(attr_warnings("node.x = x\n").len(), 1);
+    }
+
+    #[test]
+    fn special_variables() {
+        assert!(attr_warnings("node.y = NAME\nnode.z = ORDER\n").is_empty());
+    }
+
+    #[test]
+    fn scopes_are_separate() {
+        let warnings = attr_warnings("network.a = b\nnode.b = 1\n");
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].1.contains("network attribute b"));
+    }
+
+    #[test]
+    fn attribute_set_in_other_part() {
+        let ctx = TaskContext::new(None);
+        let file = "node.x = 1\nnode.y = x\nnode.z = w\nnode.w = 1\n";
+        let assigned = Assigned::new(&get_tokens(file).unwrap());
+        let part = get_tokens("node.y = x\nnode.z = w\n").unwrap();
+        let warnings = lint(&part, &ctx.functions, &ctx.network, &assigned, 11);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].offset, 20);
+    }
+
+    #[test]
+    fn same_tasks() {
+        let tokens = get_tokens("node.x = 1\nnode.y = x\n").unwrap();
+        let moved = get_tokens("\n\nnode.x = 2\nnode.y = x + 1\n").unwrap();
+        let removed = get_tokens("node.y = x\n").unwrap();
+        let assigned = Assigned::new(&tokens);
+        assert!(assigned.same_tasks(&Assigned::new(&moved)));
+        assert!(!assigned.same_tasks(&Assigned::new(&removed)));
+    }
+
+    #[test]
+    fn selection_names_are_not_attributes() {
+        assert!(attr_warnings("node[smithland].y = 1\n").is_empty());
+        assert!(attr_warnings("node[a, b, c].y = 1\n").is_empty());
+        assert!(attr_warnings("node[a -> b].y = 1\n").is_empty());
+    }
+
+    #[test]
+    fn attribute_refs() {
+        let tokens = get_tokens("node[a].x = f(y, z=w.v)\nnode(x > 1) g(k=x)\n").unwrap();
+        let refs: Vec<(usize, &str, bool)> = attr_refs(&tokens)
+            .into_iter()
+            .map(|r| (r.offset, r.name, r.set))
+            .collect();
+        assert_eq!(
+            refs,
+            vec![
+                (8, "x", true),
+                (14, "y", false),
+                (19, "w", false),
+                (29, "x", false),
+                (40, "x", false)
+            ]
+        );
+    }
+}
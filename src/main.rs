@@ -1,6 +1,13 @@
 mod colors;
+mod figures;
+mod filter;
+mod functions;
+mod lint;
 mod network;
+mod plot;
+mod styles;
 mod tokens;
+mod view;
 mod window;
 
 use gtk::gio::ApplicationFlags;
@@ -41,7 +48,9 @@ fn set_accels(app: &Application) {
     app.set_accels_for_action("win.run_func", &["<Ctrl>R"]);
     app.set_accels_for_action("win.run_line", &["<Ctrl>L"]);
     app.set_accels_for_action("win.run_buffer", &["<Ctrl>B"]);
+    app.set_accels_for_action("win.run_section", &["<Ctrl><Shift>B"]);
     app.set_accels_for_action("win.toggle_comment", &["<Alt>semicolon"]);
+    app.set_accels_for_action("win.rename_attr", &["F2"]);
     app.set_accels_for_action("win.help_line", &["<Ctrl>H"]);
 }
 
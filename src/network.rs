@@ -1,10 +1,13 @@
 use super::colors::AttrColor;
-use abi_stable::std_types::RSome;
+use super::figures::DrawAttrs;
+use super::styles::{split_styles, CellStyle, ColumnStyle, NumberFormat, Overflow, SparkType};
+use super::view::{NetworkView, NodeRows, HANDLE_SIZE};
 use cairo::Context;
 use gtk::prelude::DrawingAreaExt;
 use gtk::prelude::WidgetExt;
 use nadi_core::prelude::*;
 use nadi_core::table::{ColumnAlign, Table};
+use nadi_core::timeseries::Series;
 
 // TODO make it better later
 
@@ -12,82 +15,144 @@ const LINE_COLOR: &str = "linecolor";
 const TEXT_COLOR: &str = "textcolor";
 const LINE_WIDTH: &str = "linewidth";
 const DEFAULT_LINE_WIDTH: f64 = 1.0;
+const MATCH_COLOR: (f64, f64, f64) = (1.0, 1.0, 0.5);
+const CURRENT_MATCH_COLOR: (f64, f64, f64) = (1.0, 0.65, 0.0);
+const DIM_ALPHA: f64 = 0.2;
+const SELECTED_COLOR: (f64, f64, f64) = (0.0, 0.6, 1.0);
+const GRID_COLOR: (f64, f64, f64) = (0.8, 0.8, 0.8);
+const SPARK_WIDTH: f64 = 60.0;
+const SPARK_GAP: f64 = 4.0;
+/// dash pattern of the edges going over the hidden nodes
+const SKIP_DASH: [f64; 2] = [4.0, 3.0];
 
-pub fn calc_hw(net: &Network, ctx: &Context) -> (i32, i32) {
-    match net.attr("drawtable") {
-        Some(t) => match Table::from_attr(t) {
-            Some(t) => return calc_table_hw(net, &t, ctx).unwrap_or((100, 100)),
-            _ => (),
-        },
+pub fn calc_hw(net: &Network, attrs: &DrawAttrs, ctx: &Context, view: &NetworkView) -> (i32, i32) {
+    let rows = view.rows(net);
+    match attrs.attr("drawtable") {
+        Some(t) => {
+            let (t, styles) = split_styles(t);
+            match Table::from_attr(&t) {
+                Some(t) => {
+                    return TableLayout::new(net, attrs, &t, &styles, ctx, rows)
+                        .map(|l| l.size())
+                        .unwrap_or((100, 100))
+                }
+                _ => (),
+            }
+        }
         _ => (),
     }
-    calc_net_hw(net, ctx)
+    NetLayout::new(net, ctx, rows).size()
 }
 
-pub fn calc_net_hw(net: &Network, ctx: &Context) -> (i32, i32) {
-    ctx.set_font_size(14.0);
-    let offx = 10.0;
-    let offy = 10.0;
-    let dely = 20.0;
-    let delx = 40.0;
-    let left = offx;
-    let max_lev = net
-        .nodes()
-        .map(|n| n.lock().level())
-        .max()
-        .unwrap_or_default();
+/// Labels and their sizes for the network drawing, computed once per
+/// drawing
+pub struct NetLayout {
+    rows: NodeRows,
+    /// labels of the nodes by index
+    labels: Vec<String>,
+    max_text: f64,
+    max_level: f64,
+}
 
-    let text_start = left + delx * max_lev as f64 + offx;
-    let max_text = net
-        .nodes()
-        .map(|n| {
-            ctx.text_extents(&get_node_label(&n.lock()))
-                .unwrap()
-                .width()
-        })
-        .fold(0.0, f64::max);
-    let w = (text_start + max_text).ceil() as i32;
-    let req_height = (dely * (net.nodes_count() - 1) as f64) + offy * 2.0;
-    let h = req_height.ceil() as i32;
-    (h, w)
+impl NetLayout {
+    pub fn new(net: &Network, ctx: &Context, rows: NodeRows) -> Self {
+        ctx.set_font_size(14.0);
+        let max_level = net
+            .nodes()
+            .map(|n| n.lock().level())
+            .max()
+            .unwrap_or_default() as f64;
+        let labels: Vec<String> = net
+            .nodes()
+            .map(|n| collapsed_label(&n.lock(), &rows))
+            .collect();
+        let max_text = labels
+            .iter()
+            .map(|l| ctx.text_extents(l).unwrap().width())
+            .fold(0.0, f64::max);
+        Self {
+            rows,
+            labels,
+            max_text,
+            max_level,
+        }
+    }
+
+    pub fn size(&self) -> (i32, i32) {
+        let offx = 10.0;
+        let offy = 10.0;
+        let dely = 20.0;
+        let delx = 40.0;
+        let text_start = offx + delx * self.max_level + offx;
+        let w = (text_start + self.max_text).ceil() as i32;
+        let req_height = (dely * (self.rows.count.max(1) - 1) as f64) + offy * 2.0;
+        let h = req_height.ceil() as i32;
+        (h, w)
+    }
 }
 
 pub fn draw_network(
     net: &Network,
+    attrs: &DrawAttrs,
     ctx: &Context,
     w: i32,
     h: i32,
     darea: Option<&gtk::DrawingArea>,
+    view: &mut NetworkView,
 ) {
+    view.positions.clear();
+    view.label_areas.clear();
     if net.nodes_count() == 0 {
         return;
     }
+    view.handles.clear();
+    let rows = view.rows(net);
+    match attrs.attr("drawtable") {
+        Some(t) => {
+            let (t, styles) = split_styles(t);
+            match Table::try_from_attr(&t) {
+                Ok(t) => {
+                    match TableLayout::new(net, attrs, &t, &styles, ctx, rows) {
+                        Ok(layout) => {
+                            if let Some(da) = darea {
+                                let (h, w) = layout.size();
+                                da.set_height_request(h);
+                                da.set_width_request(w);
+                            }
+                            let _ = draw_network_table(net, &layout, ctx, w, h, darea, view);
+                        }
+                        Err(e) => {
+                            println!("{e:?}");
+                        }
+                    }
+                    return;
+                }
+                Err(e) => {
+                    println!("{e:?}");
+                }
+            }
+        }
+        _ => (),
+    }
+    let layout = NetLayout::new(net, ctx, rows);
     if let Some(da) = darea {
-        let (h, w) = calc_hw(net, ctx);
+        let (h, w) = layout.size();
         da.set_height_request(h);
         da.set_width_request(w);
     }
-    match net.attr("drawtable") {
-        Some(t) => match Table::try_from_attr(t) {
-            Ok(t) => {
-                let _ = draw_network_table(net, &t, ctx, w, h, darea);
-                return;
-            }
-            Err(e) => {
-                println!("{e:?}");
-            }
-        },
-        _ => (),
-    }
-    draw_network_only(net, ctx, w, h, darea)
+    draw_network_only(net, &layout, ctx, w, h, darea, view)
 }
+
 pub fn draw_network_only(
     net: &Network,
+    layout: &NetLayout,
     ctx: &Context,
     w: i32,
     h: i32,
     darea: Option<&gtk::DrawingArea>,
+    view: &mut NetworkView,
 ) {
+    let rows = &layout.rows;
     ctx.set_source_rgb(0.0, 0.0, 1.0);
     ctx.set_font_size(14.0);
     let offx = 10.0;
@@ -96,28 +161,15 @@ pub fn draw_network_only(
     let delx = 40.0;
     let mut top = h as f64 - offy;
     let mut left = offx;
-    let max_lev = net
-        .nodes()
-        .map(|n| n.lock().level())
-        .max()
-        .unwrap_or_default();
-
-    let mut text_start = left + delx * max_lev as f64 + offx;
-    let max_text = net
-        .nodes()
-        .map(|n| {
-            ctx.text_extents(&get_node_label(&n.lock()))
-                .unwrap()
-                .width()
-        })
-        .fold(0.0, f64::max);
+    let mut text_start = left + delx * layout.max_level + offx;
+    let max_text = layout.max_text;
     if (text_start + max_text) < w as f64 {
         left += (w as f64 - (text_start + max_text)) / 2.0;
         text_start += left - offx;
     } else if let Some(ref da) = darea {
         da.set_width_request((text_start + max_text).ceil() as i32);
     }
-    let req_height = (dely * (net.nodes_count() - 1) as f64) + offy * 2.0;
+    let req_height = (dely * (rows.count.max(1) - 1) as f64) + offy * 2.0;
     if req_height < h as f64 {
         top = (h / 2) as f64 + req_height / 2.0 - offy;
     } else if let Some(ref da) = darea {
@@ -126,83 +178,301 @@ pub fn draw_network_only(
     ctx.move_to(offx, offy);
     for n in net.nodes() {
         let n = n.lock();
+        let Some(row) = rows.row(n.index()) else {
+            continue;
+        };
         let nx = left + delx * n.level() as f64;
-        let ny = top - dely * n.index() as f64;
+        let ny = top - dely * row as f64;
+        view.positions.insert(n.index(), (nx, ny));
         ctx.move_to(nx, ny);
         _ = n.draw_color(ctx);
-        if let RSome(o) = n.output() {
+        if let Some(o) = rows.visible_output(&n) {
+            let skips = rows.skips_output(&n);
             let o = o.lock();
             ctx.move_to(nx, ny);
             set_node_color(&n, ctx, LINE_COLOR);
             set_line_width(&n, ctx, LINE_WIDTH);
+            if skips {
+                ctx.set_dash(&SKIP_DASH, 0.0);
+            }
             ctx.line_to(
                 left + delx * o.level() as f64,
-                top - dely * o.index() as f64,
+                top - dely * rows.row(o.index()).unwrap_or_default() as f64,
             );
             _ = ctx.stroke();
+            ctx.set_dash(&[], 0.0);
+        }
+        if darea.is_some() && !n.inputs().is_empty() {
+            let collapsed = rows.hidden_count(n.index()).is_some();
+            let handle = (nx + HANDLE_SIZE, ny + HANDLE_SIZE / 2.0);
+            _ = draw_collapse_handle(ctx, handle, collapsed);
+            view.handles.insert(n.index(), handle);
+        }
+        let label = &layout.labels[n.index()];
+        if let Ok(ext) = ctx.text_extents(label) {
+            view.label_areas.insert(
+                n.index(),
+                (
+                    text_start - 2.0,
+                    ny + ext.y_bearing() - 2.0,
+                    ext.width() + 4.0,
+                    ext.height() + 4.0,
+                ),
+            );
         }
         ctx.move_to(text_start, ny);
         set_node_color(&n, ctx, TEXT_COLOR);
-        let label = get_node_label(&n);
-        _ = ctx.show_text(&label);
+        _ = ctx.show_text(label);
     }
 }
 
-pub fn calc_table_hw(net: &Network, table: &Table, ctx: &Context) -> anyhow::Result<(i32, i32)> {
-    ctx.set_font_size(14.0);
-    let headers: Vec<&str> = table.columns.iter().map(|c| c.header.as_str()).collect();
-    let contents: Vec<Vec<String>> = table
-        .render_contents(&net, false)?
-        .into_iter()
-        .rev()
-        .collect();
-    let header_widths: Vec<f64> = headers
-        .iter()
-        .map(|cell| {
-            ctx.text_extents(cell)
-                .map(|et| et.width())
-                .unwrap_or_default()
-        })
-        .collect();
-    let contents_widths: Vec<Vec<f64>> = contents
-        .iter()
-        .map(|row| {
-            row.iter()
-                .map(|cell| {
-                    ctx.text_extents(cell)
-                        .map(|et| et.width())
-                        .unwrap_or_default()
-                })
-                .collect()
+/// Contents of the table cells and their sizes, computed once per
+/// drawing
+pub struct TableLayout<'a> {
+    table: &'a Table,
+    /// network attributes with the overrides of the figure
+    attrs: DrawAttrs<'a>,
+    rows: NodeRows,
+    headers: Vec<&'a str>,
+    header_widths: Vec<f64>,
+    /// style of the cells for each node in reverse order
+    styles: Vec<Vec<CellStyle>>,
+    /// rendered cells broken into lines that fit the columns
+    lines: Vec<Vec<Vec<String>>>,
+    lines_widths: Vec<Vec<Vec<f64>>>,
+    col_widths: Vec<f64>,
+    /// top line of each visible row counted from the bottom
+    row_tops: Vec<usize>,
+    /// series values of the sparkline cells for each node in reverse order
+    sparks: Vec<Vec<Option<Vec<f64>>>>,
+    spark_types: Vec<Option<SparkType>>,
+    /// group header of the columns
+    groups: Vec<Option<String>>,
+    header_bold: bool,
+    /// width of the widest ` [+N]` label of the collapsed nodes
+    hidden_width: f64,
+    max_level: f64,
+}
+
+impl<'a> TableLayout<'a> {
+    pub fn new(
+        net: &Network,
+        attrs: &DrawAttrs<'a>,
+        table: &'a Table,
+        columns: &[ColumnStyle],
+        ctx: &Context,
+        rows: NodeRows,
+    ) -> anyhow::Result<Self> {
+        ctx.set_font_size(14.0);
+        let headers: Vec<&str> = table.columns.iter().map(|c| c.header.as_str()).collect();
+        let raw: Vec<Vec<String>> = table
+            .render_contents(net, false)?
+            .into_iter()
+            .rev()
+            .collect();
+        let styles: Vec<Vec<CellStyle>> = raw
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .enumerate()
+                    .map(|(i, cell)| {
+                        columns
+                            .get(i)
+                            .map(|c| c.cell_style(cell))
+                            .unwrap_or_default()
+                    })
+                    .collect()
+            })
+            .collect();
+        let sparks: Vec<Vec<Option<Vec<f64>>>> = net
+            .nodes_rev()
+            .map(|n| {
+                let n = n.lock();
+                columns
+                    .iter()
+                    .map(|c| {
+                        c.sparkline
+                            .as_ref()
+                            .map(|s| series_values(&n, &s.series).unwrap_or_default())
+                    })
+                    .collect()
+            })
+            .collect();
+        let contents: Vec<Vec<String>> = raw
+            .into_iter()
+            .zip(&sparks)
+            .map(|(row, row_sparks)| {
+                row.into_iter()
+                    .enumerate()
+                    .map(|(i, cell)| match (columns.get(i), row_sparks.get(i)) {
+                        (Some(c), Some(Some(vals))) => match &c.sparkline {
+                            Some(s) if s.minmax => minmax_label(vals, &c.format),
+                            _ => String::new(),
+                        },
+                        (Some(c), _) => c.format.format(&cell),
+                        (None, _) => cell,
+                    })
+                    .collect()
+            })
+            .collect();
+        let header_bold = attrs.try_attr::<bool>("header_bold").unwrap_or_default();
+        let header_widths: Vec<f64> = headers
+            .iter()
+            .map(|cell| text_width(ctx, cell, header_bold))
+            .collect();
+        let lines: Vec<Vec<Vec<String>>> = contents
+            .iter()
+            .zip(&styles)
+            .map(|(row, row_styles)| {
+                row.iter()
+                    .zip(row_styles)
+                    .enumerate()
+                    .map(|(i, (cell, style))| match columns.get(i) {
+                        Some(ColumnStyle {
+                            max_width: Some(mw),
+                            overflow,
+                            sparkline: None,
+                            ..
+                        }) => fit_cell(ctx, cell, style.bold, *mw, *overflow),
+                        _ => vec![cell.to_string()],
+                    })
+                    .collect()
+            })
+            .collect();
+        let lines_widths: Vec<Vec<Vec<f64>>> = lines
+            .iter()
+            .zip(&styles)
+            .zip(&sparks)
+            .map(|((row, row_styles), row_sparks)| {
+                row.iter()
+                    .zip(row_styles)
+                    .enumerate()
+                    .map(|(i, (cell, style))| {
+                        let spark = matches!(row_sparks.get(i), Some(Some(_)));
+                        cell.iter()
+                            .map(|l| match (spark, text_width(ctx, l, style.bold)) {
+                                (true, w) if w > 0.0 => SPARK_WIDTH + SPARK_GAP + w,
+                                (true, _) => SPARK_WIDTH,
+                                (false, w) => w,
+                            })
+                            .collect()
+                    })
+                    .collect()
+            })
+            .collect();
+        let col_widths: Vec<f64> = header_widths
+            .iter()
+            .enumerate()
+            .map(|(i, &h)| {
+                lines_widths
+                    .iter()
+                    .flat_map(|row| row[i].iter().copied())
+                    .fold(h, f64::max)
+            })
+            .collect();
+        // lines is in reverse order of the nodes
+        let mut row_tops = vec![0; rows.count];
+        let mut top = 0;
+        for (ind, cells) in lines.iter().rev().enumerate() {
+            if let Some(r) = rows.row(ind) {
+                top += cells.iter().map(|c| c.len()).max().unwrap_or(1).max(1);
+                row_tops[r] = top;
+            }
+        }
+        let hidden_width = (0..net.nodes_count())
+            .filter_map(|i| rows.hidden_count(i))
+            .map(|c| text_width(ctx, &format!(" [+{c}]"), false))
+            .fold(0.0, f64::max);
+        let max_level = net.nodes().map(|n| n.lock().level()).max().unwrap_or(0) as f64;
+        Ok(Self {
+            table,
+            attrs: *attrs,
+            rows,
+            headers,
+            header_widths,
+            styles,
+            lines,
+            lines_widths,
+            col_widths,
+            row_tops,
+            sparks,
+            spark_types: columns
+                .iter()
+                .map(|c| c.sparkline.as_ref().map(|s| s.ty))
+                .collect(),
+            groups: (0..table.columns.len())
+                .map(|i| columns.get(i).and_then(|c| c.group.clone()))
+                .collect(),
+            header_bold,
+            hidden_width,
+            max_level,
         })
-        .collect();
-    let col_widths: Vec<f64> = header_widths
-        .iter()
-        .enumerate()
-        .map(|(i, &h)| contents_widths.iter().map(|row| row[i]).fold(h, f64::max))
-        .collect();
-    let offx = 10.0;
-    let dely = 20.0;
-    let delx = 40.0;
-    let twidth: f64 = col_widths.iter().sum::<f64>() + offx * (col_widths.len() + 1) as f64;
-    let max_level = net.nodes().map(|n| n.lock().level()).max().unwrap_or(0);
-    let width: f64 = delx * max_level as f64 + 2.0 * 5.0 + twidth + 2.0 * offx;
-    let height: f64 = dely * (net.nodes_count() + 2) as f64 + 2.0 * 5.0;
-    let w = width.ceil() as i32;
-    let h = height.ceil() as i32;
-    Ok((h, w))
+    }
+
+    /// number of header lines, including the group headers
+    fn header_lines(&self) -> usize {
+        if self.groups.iter().any(|g| g.is_some()) {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// number of lines in the row of the node
+    fn row_lines(&self, ind: usize) -> usize {
+        match self.rows.row(ind) {
+            Some(0) => self.row_tops.first().copied().unwrap_or(1),
+            Some(r) => self.row_tops[r] - self.row_tops[r - 1],
+            None => 0,
+        }
+    }
+
+    /// total number of lines in the visible rows
+    fn total_lines(&self) -> usize {
+        self.row_tops.iter().copied().max().unwrap_or_default()
+    }
+
+    /// line number of the top of the row from the bottom
+    fn row_top(&self, ind: usize) -> usize {
+        self.rows
+            .row(ind)
+            .and_then(|r| self.row_tops.get(r))
+            .copied()
+            .unwrap_or(1)
+    }
+
+    /// width of the table part of the drawing
+    fn twidth(&self) -> f64 {
+        let offx = 10.0;
+        self.col_widths.iter().sum::<f64>() + offx * (self.col_widths.len() + 1) as f64
+    }
+
+    pub fn size(&self) -> (i32, i32) {
+        let offx = 10.0;
+        let dely = 20.0;
+        let delx = 40.0;
+        let width: f64 =
+            delx * self.max_level + 2.0 * 5.0 + self.twidth() + self.hidden_width + 2.0 * offx;
+        let height: f64 = dely * (self.total_lines() + self.header_lines() + 1) as f64 + 2.0 * 5.0;
+        let w = width.ceil() as i32;
+        let h = height.ceil() as i32;
+        (h, w)
+    }
 }
 
 pub fn draw_network_table(
     net: &Network,
-    table: &Table,
+    layout: &TableLayout,
     ctx: &Context,
     w: i32,
     h: i32,
-    _darea: Option<&gtk::DrawingArea>,
+    darea: Option<&gtk::DrawingArea>,
+    view: &mut NetworkView,
 ) -> anyhow::Result<()> {
+    let rows = &layout.rows;
     // background
-    if let Ok(c) = net
+    if let Ok(c) = layout
+        .attrs
         .try_attr::<AttrColor>("bg_color")
         .and_then(|c| c.color())
     {
@@ -211,134 +481,518 @@ pub fn draw_network_table(
         ctx.paint()?;
         ctx.restore()?;
     }
-    if let Ok(c) = net
-        .try_attr::<AttrColor>("header_color")
-        .and_then(|c| c.color())
-    {
-        c.set(ctx);
-    } else {
-        ctx.set_source_rgb(0.0, 0.0, 1.0);
-    }
     ctx.set_font_size(14.0);
-    let headers: Vec<&str> = table.columns.iter().map(|c| c.header.as_str()).collect();
-    let contents: Vec<Vec<String>> = table
-        .render_contents(&net, false)?
-        .into_iter()
-        .rev()
-        .collect();
-    let header_widths: Vec<f64> = headers
-        .iter()
-        .map(|cell| {
-            ctx.text_extents(cell)
-                .map(|et| et.width())
-                .unwrap_or_default()
-        })
-        .collect();
-    let contents_widths: Vec<Vec<f64>> = contents
-        .iter()
-        .map(|row| {
-            row.iter()
-                .map(|cell| {
-                    ctx.text_extents(cell)
-                        .map(|et| et.width())
-                        .unwrap_or_default()
-                })
-                .collect()
-        })
-        .collect();
-    let alignments: Vec<&ColumnAlign> = table.columns.iter().map(|c| &c.align).collect();
-    let max_level = net.nodes().map(|n| n.lock().level()).max().unwrap_or(0);
-
-    let col_widths: Vec<f64> = header_widths
-        .iter()
-        .enumerate()
-        .map(|(i, &h)| contents_widths.iter().map(|row| row[i]).fold(h, f64::max))
-        .collect();
+    let headers = &layout.headers;
+    let header_widths = &layout.header_widths;
+    let col_widths = &layout.col_widths;
+    let alignments: Vec<&ColumnAlign> = layout.table.columns.iter().map(|c| &c.align).collect();
+    let max_level = layout.max_level;
     let offx = 10.0;
     let dely = 20.0;
     let delx = 40.0;
     let mut height = h as f64;
     let width = w as f64;
-    let twidth: f64 = col_widths.iter().sum::<f64>() + offx * (col_widths.len() + 1) as f64;
-    let req_width = delx * max_level as f64 + 2.0 * 5.0 + twidth;
-    let req_ht: f64 = dely * (net.nodes_count() + 2) as f64 + 2.0 * 5.0;
+    let twidth: f64 = layout.twidth();
+    let req_width = delx * max_level + 2.0 * 5.0 + twidth + layout.hidden_width;
+    let header_lines = layout.header_lines() as f64;
+    let req_ht: f64 = dely * (layout.total_lines() as f64 + header_lines + 1.0) + 2.0 * 5.0;
     let offset = (width - req_width) / 2.0;
-    let txtstart = offset + delx * max_level as f64 + 2.0 * 5.0;
+    let txtstart = offset + delx * max_level + 2.0 * 5.0;
     let offset_y = (height - req_ht) / 2.0;
     height -= offset_y;
     let col_stops: Vec<f64> = (0..(col_widths.len()))
         .map(|i| col_widths[0..i].iter().sum::<f64>() + offx * (i + 1) as f64 + txtstart)
         .collect();
+    let header_bottom = offset_y + dely * (header_lines + 0.5);
+    let table_bottom = height - dely * 0.25;
+    draw_table_background(net, layout, ctx, (offset, txtstart + twidth), height)?;
+    if let Ok(c) = layout
+        .attrs
+        .try_attr::<AttrColor>("header_bg_color")
+        .and_then(|c| c.color())
+    {
+        c.set(ctx);
+        ctx.rectangle(
+            txtstart,
+            offset_y + dely * 0.25,
+            twidth,
+            dely * header_lines + dely * 0.25,
+        );
+        ctx.fill()?;
+    }
+    if layout.attrs.try_attr::<bool>("vgrid").unwrap_or_default() {
+        set_grid_color(&layout.attrs, ctx);
+        ctx.set_line_width(DEFAULT_LINE_WIDTH);
+        for stop in col_stops.iter().skip(1) {
+            ctx.move_to(stop - offx / 2.0, offset_y + dely * 0.25);
+            ctx.line_to(stop - offx / 2.0, table_bottom);
+        }
+        ctx.stroke()?;
+    }
+    if let Ok(c) = layout
+        .attrs
+        .try_attr::<AttrColor>("header_color")
+        .and_then(|c| c.color())
+    {
+        c.set(ctx);
+    } else {
+        ctx.set_source_rgb(0.0, 0.0, 1.0);
+    }
+    // group headers spanning the columns
+    let mut start = 0;
+    while start < layout.groups.len() {
+        let group = &layout.groups[start];
+        let end = start
+            + layout.groups[start..]
+                .iter()
+                .take_while(|g| *g == group)
+                .count();
+        if let Some(g) = group {
+            let left = col_stops[start];
+            let right = col_stops[end - 1] + col_widths[end - 1];
+            let gw = text_width(ctx, g, layout.header_bold);
+            ctx.move_to(left + (right - left - gw) / 2.0, offset_y + dely);
+            show_text_bold(ctx, g, layout.header_bold)?;
+            ctx.move_to(left, offset_y + dely * 1.25);
+            ctx.line_to(right, offset_y + dely * 1.25);
+            ctx.stroke()?;
+        }
+        start = end;
+    }
     for (i, (head, a)) in headers.iter().zip(&alignments).enumerate() {
         let stop = match a {
             ColumnAlign::Left => col_stops[i],
             ColumnAlign::Right => col_stops[i] + col_widths[i] - header_widths[i],
             ColumnAlign::Center => col_stops[i] + (col_widths[i] - header_widths[i]) / 2.0,
         };
-        ctx.move_to(stop, offset_y + dely);
-        ctx.show_text(head)?;
+        ctx.move_to(stop, offset_y + dely * header_lines);
+        show_text_bold(ctx, head, layout.header_bold)?;
     }
-    ctx.move_to(offset, offset_y + dely * 1.5);
-    ctx.line_to(txtstart + twidth, offset_y + dely * 1.5);
+    ctx.move_to(offset, header_bottom);
+    ctx.line_to(txtstart + twidth, header_bottom);
     ctx.stroke()?;
+    if layout.attrs.try_attr::<bool>("hgrid").unwrap_or_default() {
+        set_grid_color(&layout.attrs, ctx);
+        ctx.set_line_width(DEFAULT_LINE_WIDTH);
+        for n in net.nodes() {
+            let ind = n.lock().index();
+            if rows.row(ind).is_some() {
+                let y = height - layout.row_top(ind) as f64 * dely
+                    + (layout.row_lines(ind) as f64 - 0.75) * dely;
+                ctx.move_to(txtstart, y);
+                ctx.line_to(txtstart + twidth, y);
+            }
+        }
+        ctx.stroke()?;
+    }
     net.nodes_rev()
-        .zip(contents)
-        .zip(contents_widths)
-        .try_for_each(|((n, row), row_widths)| -> cairo::Result<()> {
-            let n = n.lock();
-            let y = height - (n.index() + 1) as f64 * dely;
-            let x = offset + n.level() as f64 * delx + offx / 2.0;
-
-            if let RSome(o) = n.output() {
-                set_node_color(&n, ctx, LINE_COLOR);
-                let o = o.lock();
-                let yo = height - (o.index() + 1) as f64 * dely;
-                let xo = offset + o.level() as f64 * delx + offx / 2.0;
-                let dx = xo - x;
-                let dy = yo - y;
-                let l = (dx.powi(2) + dy.powi(2)).sqrt();
-                let (ux, uy) = (dx / l, dy / l);
-                let (sx, sy) = (x + ux * 5.0 * 1.4, y + uy * 5.0 * 1.4);
-                let (ex, ey) = (xo - ux * 5.0 * 1.4, yo - uy * 5.0 * 1.4);
-                set_line_width(&n, ctx, LINE_WIDTH);
-                ctx.move_to(sx, sy);
-                ctx.line_to(ex, ey);
-                ctx.stroke()?;
-                let (asx, asy) = (ex - ux * 5.0, ey - uy * 5.0);
-                let (aex, aey) = (xo - ux * 5.0, yo - uy * 5.0);
-                ctx.set_line_width(DEFAULT_LINE_WIDTH);
-                ctx.move_to(asx + uy * 5.0 * 0.5, asy - ux * 5.0 * 0.5);
-                ctx.line_to(aex, aey);
-                ctx.line_to(asx - uy * 5.0 * 0.5, asy + ux * 5.0 * 0.5);
-                ctx.line_to(asx + ux, asy + uy);
-                ctx.fill()?;
-                ctx.stroke()?;
+        .zip(&layout.lines)
+        .zip(&layout.lines_widths)
+        .zip(&layout.styles)
+        .zip(&layout.sparks)
+        .try_for_each(
+            |((((n, row), row_widths), row_styles), row_sparks)| -> cairo::Result<()> {
+                let n = n.lock();
+                if rows.row(n.index()).is_none() {
+                    return Ok(());
+                }
+                let y = height - layout.row_top(n.index()) as f64 * dely;
+                let row_height = layout.row_lines(n.index()) as f64 * dely;
+                let x = offset + n.level() as f64 * delx + offx / 2.0;
+                view.positions.insert(n.index(), (x, y));
+                view.label_areas
+                    .insert(n.index(), (txtstart, y - dely * 0.75, twidth, row_height));
+
+                if let Some(o) = rows.visible_output(&n) {
+                    set_node_color(&n, ctx, LINE_COLOR);
+                    let skips = rows.skips_output(&n);
+                    let o = o.lock();
+                    let yo = height - layout.row_top(o.index()) as f64 * dely;
+                    let xo = offset + o.level() as f64 * delx + offx / 2.0;
+                    let dx = xo - x;
+                    let dy = yo - y;
+                    let l = (dx.powi(2) + dy.powi(2)).sqrt();
+                    let (ux, uy) = (dx / l, dy / l);
+                    let (sx, sy) = (x + ux * 5.0 * 1.4, y + uy * 5.0 * 1.4);
+                    let (ex, ey) = (xo - ux * 5.0 * 1.4, yo - uy * 5.0 * 1.4);
+                    set_line_width(&n, ctx, LINE_WIDTH);
+                    if skips {
+                        ctx.set_dash(&SKIP_DASH, 0.0);
+                    }
+                    ctx.move_to(sx, sy);
+                    ctx.line_to(ex, ey);
+                    ctx.stroke()?;
+                    ctx.set_dash(&[], 0.0);
+                    let (asx, asy) = (ex - ux * 5.0, ey - uy * 5.0);
+                    let (aex, aey) = (xo - ux * 5.0, yo - uy * 5.0);
+                    ctx.set_line_width(DEFAULT_LINE_WIDTH);
+                    ctx.move_to(asx + uy * 5.0 * 0.5, asy - ux * 5.0 * 0.5);
+                    ctx.line_to(aex, aey);
+                    ctx.line_to(asx - uy * 5.0 * 0.5, asy + ux * 5.0 * 0.5);
+                    ctx.line_to(asx + ux, asy + uy);
+                    ctx.fill()?;
+                    ctx.stroke()?;
+                }
+                // if highlight.contains(&n.index()){
+                // 	ctx.set_source_rgb(0.6, 0.35, 0.35);
+                // } else {
+                // 	ctx.set_source_rgb(0.35, 0.35, 0.6);
+                // }
+                ctx.move_to(x, y);
+                n.draw_color(ctx)?;
+                if darea.is_some() && !n.inputs().is_empty() {
+                    let collapsed = rows.hidden_count(n.index()).is_some();
+                    let handle = (x + HANDLE_SIZE, y + HANDLE_SIZE / 2.0);
+                    draw_collapse_handle(ctx, handle, collapsed)?;
+                    view.handles.insert(n.index(), handle);
+                }
+                // set_node_color(&n, ctx, NODE_COLOR);
+                // ctx.arc(x, y, 5.0, 0.0, 2.0 * 3.1416);
+                // ctx.fill()?;
+                // ctx.stroke()?;
+
+                for (i, ((cell, a), style)) in
+                    row.iter().zip(&alignments).zip(row_styles).enumerate()
+                {
+                    if let Some(bg) = &style.bgcolor {
+                        bg.set(ctx);
+                        ctx.rectangle(
+                            col_stops[i] - offx / 2.0,
+                            y - dely * 0.75,
+                            col_widths[i] + offx,
+                            row_height,
+                        );
+                        ctx.fill()?;
+                    }
+                    match &style.color {
+                        Some(c) => c.set(ctx),
+                        None => set_node_color(&n, ctx, TEXT_COLOR),
+                    }
+                    for (j, (line, lw)) in cell.iter().zip(&row_widths[i]).enumerate() {
+                        let stop = match a {
+                            ColumnAlign::Left => col_stops[i],
+                            ColumnAlign::Right => col_stops[i] + col_widths[i] - lw,
+                            ColumnAlign::Center => col_stops[i] + (col_widths[i] - lw) / 2.0,
+                        };
+                        let stop = match (j, row_sparks.get(i)) {
+                            (0, Some(Some(vals))) => {
+                                let ty = layout.spark_types[i].unwrap_or_default();
+                                draw_sparkline(ctx, vals, ty, stop, y, dely)?;
+                                stop + SPARK_WIDTH + SPARK_GAP
+                            }
+                            _ => stop,
+                        };
+                        ctx.move_to(stop, y + j as f64 * dely);
+                        show_text_bold(ctx, line, style.bold)?;
+                    }
+                }
+                set_node_color(&n, ctx, TEXT_COLOR);
+                if let Some(c) = rows.hidden_count(n.index()) {
+                    ctx.move_to(txtstart + twidth, y);
+                    ctx.show_text(&format!(" [+{c}]"))?;
+                }
+                Ok(())
+            },
+        )?;
+    Ok(())
+}
+
+/// Small chart of the series values inside a table cell
+fn draw_sparkline(
+    ctx: &Context,
+    vals: &[f64],
+    ty: SparkType,
+    x: f64,
+    y: f64,
+    dely: f64,
+) -> cairo::Result<()> {
+    let finite = vals.iter().copied().filter(|v| v.is_finite());
+    let (min, max) = finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(a, b), v| {
+        (a.min(v), b.max(v))
+    });
+    if vals.is_empty() || min > max {
+        return Ok(());
+    }
+    let top = y - dely * 0.6;
+    let ht = dely * 0.7;
+    let range = if max > min { max - min } else { 1.0 };
+    let ypos = |v: f64| top + ht - (v - min) / range * ht;
+    ctx.save()?;
+    ctx.set_line_width(DEFAULT_LINE_WIDTH);
+    match ty {
+        SparkType::Line => {
+            let dx = SPARK_WIDTH / (vals.len().max(2) - 1) as f64;
+            let mut pen_down = false;
+            for (i, v) in vals.iter().enumerate() {
+                if !v.is_finite() {
+                    pen_down = false;
+                    continue;
+                }
+                let px = x + i as f64 * dx;
+                if pen_down {
+                    ctx.line_to(px, ypos(*v));
+                } else {
+                    ctx.move_to(px, ypos(*v));
+                    pen_down = true;
+                }
             }
-            // if highlight.contains(&n.index()){
-            // 	ctx.set_source_rgb(0.6, 0.35, 0.35);
-            // } else {
-            // 	ctx.set_source_rgb(0.35, 0.35, 0.6);
-            // }
-            ctx.move_to(x, y);
-            n.draw_color(ctx)?;
-            // set_node_color(&n, ctx, NODE_COLOR);
-            // ctx.arc(x, y, 5.0, 0.0, 2.0 * 3.1416);
-            // ctx.fill()?;
-            // ctx.stroke()?;
-
-            set_node_color(&n, ctx, TEXT_COLOR);
-            for (i, (cell, a)) in row.iter().zip(&alignments).enumerate() {
-                let stop = match a {
-                    ColumnAlign::Left => col_stops[i],
-                    ColumnAlign::Right => col_stops[i] + col_widths[i] - row_widths[i],
-                    ColumnAlign::Center => col_stops[i] + (col_widths[i] - row_widths[i]) / 2.0,
+            ctx.stroke()?;
+        }
+        SparkType::Bar => {
+            let bw = SPARK_WIDTH / vals.len() as f64;
+            // bars start from zero when the values cross it
+            let base = ypos(0.0f64.clamp(min, max));
+            for (i, v) in vals.iter().enumerate().filter(|(_, v)| v.is_finite()) {
+                let vy = ypos(*v);
+                ctx.rectangle(
+                    x + i as f64 * bw,
+                    vy.min(base),
+                    (bw * 0.8).max(0.5),
+                    (vy - base).abs().max(0.5),
+                );
+            }
+            ctx.fill()?;
+        }
+    }
+    ctx.restore()
+}
+
+/// Range of the series shown next to the sparkline
+fn minmax_label(vals: &[f64], format: &NumberFormat) -> String {
+    let finite = vals.iter().copied().filter(|v| v.is_finite());
+    let (min, max) = finite.fold((f64::INFINITY, f64::NEG_INFINITY), |(a, b), v| {
+        (a.min(v), b.max(v))
+    });
+    if min > max {
+        return String::new();
+    }
+    format!(
+        "{}–{}",
+        format.format(&min.to_string()),
+        format.format(&max.to_string())
+    )
+}
+
+/// Values of the numeric series of the node
+pub fn series_values(node: &NodeInner, name: &str) -> Option<Vec<f64>> {
+    match node.series(name)? {
+        Series::Floats(v) => Some(v.to_vec()),
+        Series::Integers(v) => Some(v.iter().map(|i| *i as f64).collect()),
+        Series::Booleans(v) => Some(v.iter().map(|b| *b as u8 as f64).collect()),
+        _ => None,
+    }
+}
+
+/// Alternating background of the rows in the table
+fn draw_table_background(
+    net: &Network,
+    layout: &TableLayout,
+    ctx: &Context,
+    (left, right): (f64, f64),
+    height: f64,
+) -> cairo::Result<()> {
+    let Ok(stripe) = layout
+        .attrs
+        .try_attr::<AttrColor>("stripe_color")
+        .and_then(|c| c.color())
+    else {
+        return Ok(());
+    };
+    let dely = 20.0;
+    stripe.set(ctx);
+    for n in net.nodes() {
+        let ind = n.lock().index();
+        if let Some(r) = layout.rows.row(ind) {
+            if r % 2 == 1 {
+                let y = height - layout.row_top(ind) as f64 * dely;
+                ctx.rectangle(
+                    left,
+                    y - dely * 0.75,
+                    right - left,
+                    layout.row_lines(ind) as f64 * dely,
+                );
+            }
+        }
+    }
+    ctx.fill()
+}
+
+fn set_grid_color(attrs: &DrawAttrs, ctx: &Context) {
+    match attrs
+        .try_attr::<AttrColor>("grid_color")
+        .and_then(|c| c.color())
+    {
+        Ok(c) => c.set(ctx),
+        Err(_) => {
+            let (r, g, b) = GRID_COLOR;
+            ctx.set_source_rgb(r, g, b);
+        }
+    }
+}
+
+/// Break the cell into lines or truncate it to fit in the `max_width`
+fn fit_cell(
+    ctx: &cairo::Context,
+    cell: &str,
+    bold: bool,
+    max_width: f64,
+    overflow: Overflow,
+) -> Vec<String> {
+    if text_width(ctx, cell, bold) <= max_width {
+        return vec![cell.to_string()];
+    }
+    match overflow {
+        Overflow::Ellipsis => {
+            let mut chars: Vec<char> = cell.chars().collect();
+            while !chars.is_empty() {
+                chars.pop();
+                let txt: String = chars.iter().chain(['…'].iter()).collect();
+                if text_width(ctx, &txt, bold) <= max_width {
+                    return vec![txt];
+                }
+            }
+            vec!["…".to_string()]
+        }
+        Overflow::Wrap => {
+            let mut lines = vec![];
+            let mut line = String::new();
+            for word in cell.split_whitespace() {
+                let next = if line.is_empty() {
+                    word.to_string()
+                } else {
+                    format!("{line} {word}")
                 };
-                ctx.move_to(stop, y);
-                ctx.show_text(cell)?;
+                if text_width(ctx, &next, bold) <= max_width {
+                    line = next;
+                    continue;
+                }
+                if !line.is_empty() {
+                    lines.push(std::mem::take(&mut line));
+                }
+                // words longer than the width are broken between characters
+                for c in word.chars() {
+                    line.push(c);
+                    if line.chars().count() > 1 && text_width(ctx, &line, bold) > max_width {
+                        line.pop();
+                        lines.push(std::mem::replace(&mut line, c.to_string()));
+                    }
+                }
             }
-            Ok(())
-        })?;
-    Ok(())
+            lines.push(line);
+            lines
+        }
+    }
+}
+
+fn text_width(ctx: &cairo::Context, text: &str, bold: bool) -> f64 {
+    if bold {
+        _ = ctx.save();
+        set_bold(ctx);
+    }
+    let width = ctx
+        .text_extents(text)
+        .map(|et| et.width())
+        .unwrap_or_default();
+    if bold {
+        _ = ctx.restore();
+    }
+    width
+}
+
+fn show_text_bold(ctx: &cairo::Context, text: &str, bold: bool) -> cairo::Result<()> {
+    if !bold {
+        return ctx.show_text(text);
+    }
+    ctx.save()?;
+    set_bold(ctx);
+    ctx.show_text(text)?;
+    ctx.restore()
+}
+
+fn set_bold(ctx: &cairo::Context) {
+    ctx.select_font_face(
+        "sans-serif",
+        cairo::FontSlant::Normal,
+        cairo::FontWeight::Bold,
+    );
+}
+
+/// Ring around the selected node
+fn draw_selection(ctx: &cairo::Context, x: f64, y: f64) -> cairo::Result<()> {
+    let (r, g, b) = SELECTED_COLOR;
+    ctx.save()?;
+    ctx.new_sub_path();
+    ctx.set_source_rgb(r, g, b);
+    ctx.set_line_width(2.0);
+    ctx.arc(x, y, 8.0, 0.0, 2.0 * std::f64::consts::PI);
+    ctx.stroke()?;
+    ctx.restore()
+}
+
+/// Box with `-` to collapse the subtree, or `+` to expand it
+fn draw_collapse_handle(
+    ctx: &cairo::Context,
+    (x, y): (f64, f64),
+    collapsed: bool,
+) -> cairo::Result<()> {
+    let half = HANDLE_SIZE / 2.0;
+    ctx.save()?;
+    ctx.set_line_width(DEFAULT_LINE_WIDTH);
+    ctx.set_source_rgb(1.0, 1.0, 1.0);
+    ctx.rectangle(x - half, y - half, HANDLE_SIZE, HANDLE_SIZE);
+    ctx.fill_preserve()?;
+    ctx.set_source_rgb(0.4, 0.4, 0.4);
+    ctx.stroke()?;
+    ctx.move_to(x - half + 2.0, y);
+    ctx.line_to(x + half - 2.0, y);
+    if collapsed {
+        ctx.move_to(x, y - half + 2.0);
+        ctx.line_to(x, y + half - 2.0);
+    }
+    ctx.stroke()?;
+    ctx.restore()
+}
+
+/// Highlighted path, search matches and the selection drawn over the
+/// cached drawing, so changing them doesn't need a new layout
+pub fn draw_overlay(ctx: &Context, view: &NetworkView) -> cairo::Result<()> {
+    ctx.save()?;
+    if view.highlight.is_some() {
+        // fade the nodes outside the highlight, the rows are merged into
+        // one path so they are not faded twice where they touch
+        ctx.set_operator(cairo::Operator::DestOut);
+        ctx.set_source_rgba(0.0, 0.0, 0.0, 1.0 - DIM_ALPHA);
+        for (ind, &(x, y)) in view.positions.iter().filter(|(i, _)| view.is_dimmed(**i)) {
+            let (lx, ly, lw, lh) = view
+                .label_areas
+                .get(ind)
+                .copied()
+                .unwrap_or((x, y, 0.0, 0.0));
+            let top = ly.min(y - 10.0);
+            let bottom = (ly + lh).max(y + 10.0);
+            ctx.rectangle(x - 10.0, top, lx + lw - x + 10.0, bottom - top);
+        }
+        ctx.fill()?;
+        ctx.set_operator(cairo::Operator::Over);
+    }
+    for ind in &view.matches {
+        let Some(&(x, y, w, h)) = view.label_areas.get(ind) else {
+            continue;
+        };
+        let (r, g, b) = if view.current_match() == Some(*ind) {
+            CURRENT_MATCH_COLOR
+        } else {
+            MATCH_COLOR
+        };
+        // translucent so the label stays readable under it
+        ctx.set_source_rgba(r, g, b, 0.5);
+        ctx.rectangle(x, y, w, h);
+        ctx.fill()?;
+    }
+    if let Some(&(x, y)) = view.selected.and_then(|s| view.positions.get(&s)) {
+        draw_selection(ctx, x, y)?;
+    }
+    ctx.restore()
 }
 
 fn set_node_color(node: &NodeInner, ctx: &cairo::Context, attr: &str) {
@@ -358,7 +1012,16 @@ fn set_line_width(node: &NodeInner, ctx: &cairo::Context, attr: &str) {
     ctx.set_line_width(w)
 }
 
-fn get_node_label(node: &NodeInner) -> String {
+/// Node label with the number of hidden nodes for collapsed subtrees
+fn collapsed_label(node: &NodeInner, rows: &NodeRows) -> String {
+    let label = get_node_label(node);
+    match rows.hidden_count(node.index()) {
+        Some(c) => format!("{label} [+{c}]"),
+        None => label,
+    }
+}
+
+pub fn get_node_label(node: &NodeInner) -> String {
     let l = node
         .try_attr::<String>("nodelabel")
         .unwrap_or(node.name().to_string());
@@ -0,0 +1,713 @@
+use super::network::series_values;
+use cairo::Context;
+use nadi_core::attrs::Attribute;
+use nadi_core::prelude::*;
+use nadi_core::timeseries::Series;
+
+const MARGIN_LEFT: f64 = 70.0;
+const MARGIN_RIGHT: f64 = 20.0;
+const MARGIN_TOP: f64 = 35.0;
+const MARGIN_BOTTOM: f64 = 45.0;
+const TICKS: usize = 6;
+const POINT_RADIUS: f64 = 3.0;
+/// Most bins a histogram can have
+const MAX_BINS: usize = 1000;
+const PALETTE: [(f64, f64, f64); 8] = [
+    (0.12, 0.47, 0.71),
+    (1.0, 0.5, 0.05),
+    (0.17, 0.63, 0.17),
+    (0.84, 0.15, 0.16),
+    (0.58, 0.4, 0.74),
+    (0.55, 0.34, 0.29),
+    (0.89, 0.47, 0.76),
+    (0.5, 0.5, 0.5),
+];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum PlotKind {
+    #[default]
+    Line,
+    Scatter,
+    /// bars starting at the x of the points with the `bin_width`
+    Histogram,
+}
+
+/// How the values on the axis are shown
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum AxisScale {
+    #[default]
+    Number,
+    /// seconds since the unix epoch
+    Time,
+}
+
+/// Values of a single node in the plot
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub label: String,
+    pub points: Vec<(f64, f64)>,
+    /// node index of each point, empty if the points are not nodes
+    pub nodes: Vec<usize>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Plot {
+    pub title: String,
+    pub kind: PlotKind,
+    pub xlabel: String,
+    pub ylabel: String,
+    pub xscale: AxisScale,
+    pub traces: Vec<Trace>,
+    pub bin_width: f64,
+}
+
+impl Plot {
+    /// Plot of the `series` of the nodes, against the `xseries` if
+    /// given or the position of the values otherwise
+    pub fn series(
+        net: &Network,
+        nodes: &[usize],
+        series: &str,
+        xseries: Option<&str>,
+        kind: PlotKind,
+    ) -> anyhow::Result<Self> {
+        let mut xscale = AxisScale::Number;
+        let mut traces = vec![];
+        for &ind in nodes {
+            let Some(node) = net.node(ind) else {
+                continue;
+            };
+            let node = node.lock();
+            let Some(y) = series_values(&node, series) else {
+                continue;
+            };
+            let x = match xseries {
+                Some(xs) => match axis_values(&node, xs) {
+                    Some((x, scale)) => {
+                        xscale = scale;
+                        x
+                    }
+                    None => anyhow::bail!("Node {} has no series {xs:?}", node.name()),
+                },
+                None => (0..y.len()).map(|i| i as f64).collect(),
+            };
+            traces.push(Trace {
+                label: node.name().to_string(),
+                points: x.into_iter().zip(y).collect(),
+                nodes: vec![],
+            });
+        }
+        if traces.is_empty() {
+            anyhow::bail!("None of the nodes have a numeric series {series:?}");
+        }
+        Ok(Self {
+            title: series.to_string(),
+            kind,
+            xlabel: xseries.unwrap_or("index").to_string(),
+            ylabel: series.to_string(),
+            xscale,
+            traces,
+            ..Default::default()
+        })
+    }
+
+    /// Scatter plot of two attributes with a point for each node
+    pub fn attributes(
+        net: &Network,
+        nodes: &[usize],
+        xattr: &str,
+        yattr: &str,
+    ) -> anyhow::Result<Self> {
+        let mut points = vec![];
+        let mut inds = vec![];
+        for &ind in nodes {
+            let Some(node) = net.node(ind) else {
+                continue;
+            };
+            let node = node.lock();
+            if let (Some(x), Some(y)) = (attr_value(&node, xattr), attr_value(&node, yattr)) {
+                points.push((x, y));
+                inds.push(ind);
+            }
+        }
+        if points.is_empty() {
+            anyhow::bail!("None of the nodes have numeric attributes {xattr:?} and {yattr:?}");
+        }
+        Ok(Self {
+            title: format!("{yattr} vs {xattr}"),
+            kind: PlotKind::Scatter,
+            xlabel: xattr.to_string(),
+            ylabel: yattr.to_string(),
+            traces: vec![Trace {
+                label: String::new(),
+                points,
+                nodes: inds,
+            }],
+            ..Default::default()
+        })
+    }
+
+    /// Histogram of the attribute values of the nodes
+    pub fn histogram(net: &Network, nodes: &[usize], attr: &str) -> anyhow::Result<Self> {
+        let vals: Vec<f64> = nodes
+            .iter()
+            .filter_map(|&i| net.node(i))
+            .filter_map(|n| attr_value(&n.lock(), attr))
+            .filter(|v| v.is_finite())
+            .collect();
+        let Some((start, bin_width, counts)) = histogram_bins(&vals) else {
+            anyhow::bail!("None of the nodes have a numeric attribute {attr:?}");
+        };
+        Ok(Self {
+            title: format!("Histogram of {attr}"),
+            kind: PlotKind::Histogram,
+            xlabel: attr.to_string(),
+            ylabel: "count".to_string(),
+            traces: vec![Trace {
+                label: String::new(),
+                points: counts
+                    .into_iter()
+                    .enumerate()
+                    .map(|(i, c)| (start + i as f64 * bin_width, c as f64))
+                    .collect(),
+                nodes: vec![],
+            }],
+            bin_width,
+            ..Default::default()
+        })
+    }
+}
+
+/// Start, width and the counts of the histogram bins of the values
+fn histogram_bins(vals: &[f64]) -> Option<(f64, f64, Vec<usize>)> {
+    let (min, max) = vals
+        .iter()
+        .fold(None, |b: Option<(f64, f64)>, &v| match b {
+            Some((lo, hi)) => Some((lo.min(v), hi.max(v))),
+            None => Some((v, v)),
+        })?;
+    let bin_width = if max > min {
+        // square root rule for the number of bins
+        let bins = (vals.len() as f64).sqrt().ceil().max(1.0);
+        nice_step((max - min) / bins)
+    } else if min != 0.0 {
+        // a single bin around the same values
+        nice_step(min.abs())
+    } else {
+        1.0
+    };
+    let start = (min / bin_width).floor() * bin_width;
+    let nbins = (((max - start) / bin_width).floor() as usize + 1).min(MAX_BINS);
+    let mut counts = vec![0usize; nbins];
+    for v in vals {
+        let b = ((v - start) / bin_width).floor() as usize;
+        counts[b.min(nbins - 1)] += 1;
+    }
+    Some((start, bin_width, counts))
+}
+
+/// Numeric value of the node attribute
+fn attr_value(node: &NodeInner, name: &str) -> Option<f64> {
+    match node.attr(name)? {
+        Attribute::Float(f) => Some(*f),
+        Attribute::Integer(i) => Some(*i as f64),
+        Attribute::Bool(b) => Some(*b as u8 as f64),
+        _ => None,
+    }
+}
+
+/// Values of the series usable for the axis, dates are converted to
+/// seconds since the unix epoch
+fn axis_values(node: &NodeInner, name: &str) -> Option<(Vec<f64>, AxisScale)> {
+    if let Some(v) = series_values(node, name) {
+        return Some((v, AxisScale::Number));
+    }
+    let vals: Vec<f64> = match node.series(name)? {
+        Series::Dates(v) => v.iter().map(|d| parse_time(&d.to_string())).collect(),
+        Series::DateTimes(v) => v.iter().map(|d| parse_time(&d.to_string())).collect(),
+        Series::Strings(v) => v.iter().map(|d| parse_time(d.as_str())).collect(),
+        _ => return None,
+    };
+    Some((vals, AxisScale::Time))
+}
+
+/// Area of the plot with the ranges of the axes
+struct Frame {
+    xticks: Vec<f64>,
+    yticks: Vec<f64>,
+    xrange: (f64, f64),
+    yrange: (f64, f64),
+    pw: f64,
+    ph: f64,
+}
+
+impl Frame {
+    fn new(plot: &Plot, w: f64, h: f64) -> Option<Self> {
+        let pw = w - MARGIN_LEFT - MARGIN_RIGHT;
+        let ph = h - MARGIN_TOP - MARGIN_BOTTOM;
+        if pw <= 0.0 || ph <= 0.0 {
+            return None;
+        }
+        let points = plot.traces.iter().flat_map(|t| t.points.iter());
+        let ((xmin, mut xmax), (mut ymin, ymax)) = bounds(points)?;
+        if plot.kind == PlotKind::Histogram {
+            xmax += plot.bin_width;
+            ymin = 0.0;
+        }
+        let xticks = match plot.xscale {
+            AxisScale::Number => nice_ticks(xmin, xmax),
+            AxisScale::Time => time_ticks(xmin, xmax),
+        };
+        let yticks = nice_ticks(ymin, ymax);
+        Some(Self {
+            xrange: span(&xticks, xmin, xmax),
+            yrange: span(&yticks, ymin, ymax),
+            xticks,
+            yticks,
+            pw,
+            ph,
+        })
+    }
+
+    fn px(&self, x: f64) -> f64 {
+        let (xmin, xmax) = self.xrange;
+        MARGIN_LEFT + (x - xmin) / (xmax - xmin) * self.pw
+    }
+
+    fn py(&self, y: f64) -> f64 {
+        let (ymin, ymax) = self.yrange;
+        MARGIN_TOP + self.ph - (y - ymin) / (ymax - ymin) * self.ph
+    }
+}
+
+pub fn draw_plot(plot: &Plot, ctx: &Context, w: i32, h: i32) -> cairo::Result<()> {
+    let (w, h) = (w as f64, h as f64);
+    ctx.set_source_rgb(1.0, 1.0, 1.0);
+    ctx.paint()?;
+    ctx.set_source_rgb(0.0, 0.0, 0.0);
+    ctx.set_line_width(1.0);
+    ctx.set_font_size(14.0);
+    let title_w = ctx.text_extents(&plot.title)?.width();
+    ctx.move_to((w - title_w) / 2.0, MARGIN_TOP - 12.0);
+    ctx.show_text(&plot.title)?;
+
+    let Some(frame) = Frame::new(plot, w, h) else {
+        ctx.move_to(MARGIN_LEFT, MARGIN_TOP + 20.0);
+        return ctx.show_text("No data to plot");
+    };
+    let (pw, ph) = (frame.pw, frame.ph);
+    let px = |x: f64| frame.px(x);
+    let py = |y: f64| frame.py(y);
+    let (xticks, yticks) = (&frame.xticks, &frame.yticks);
+
+    // axes
+    ctx.rectangle(MARGIN_LEFT, MARGIN_TOP, pw, ph);
+    ctx.stroke()?;
+    ctx.set_font_size(11.0);
+    let xstep = step(xticks);
+    for &t in xticks {
+        let x = px(t);
+        ctx.move_to(x, MARGIN_TOP + ph);
+        ctx.line_to(x, MARGIN_TOP + ph + 4.0);
+        ctx.stroke()?;
+        let label = match plot.xscale {
+            AxisScale::Number => format_number(t, xstep),
+            AxisScale::Time => format_time(t, xstep),
+        };
+        let lw = ctx.text_extents(&label)?.width();
+        ctx.move_to(x - lw / 2.0, MARGIN_TOP + ph + 16.0);
+        ctx.show_text(&label)?;
+    }
+    let ystep = step(yticks);
+    for &t in yticks {
+        let y = py(t);
+        ctx.move_to(MARGIN_LEFT - 4.0, y);
+        ctx.line_to(MARGIN_LEFT, y);
+        ctx.stroke()?;
+        let label = format_number(t, ystep);
+        let lw = ctx.text_extents(&label)?.width();
+        ctx.move_to(MARGIN_LEFT - 6.0 - lw, y + 4.0);
+        ctx.show_text(&label)?;
+    }
+    ctx.set_font_size(12.0);
+    let lw = ctx.text_extents(&plot.xlabel)?.width();
+    ctx.move_to(MARGIN_LEFT + (pw - lw) / 2.0, h - 8.0);
+    ctx.show_text(&plot.xlabel)?;
+    let lw = ctx.text_extents(&plot.ylabel)?.width();
+    ctx.save()?;
+    ctx.move_to(16.0, MARGIN_TOP + (ph + lw) / 2.0);
+    ctx.rotate(-std::f64::consts::FRAC_PI_2);
+    ctx.show_text(&plot.ylabel)?;
+    ctx.restore()?;
+
+    // data
+    ctx.save()?;
+    ctx.rectangle(MARGIN_LEFT, MARGIN_TOP, pw, ph);
+    ctx.clip();
+    for (i, trace) in plot.traces.iter().enumerate() {
+        let (r, g, b) = PALETTE[i % PALETTE.len()];
+        ctx.set_source_rgb(r, g, b);
+        match plot.kind {
+            PlotKind::Line => {
+                ctx.set_line_width(1.5);
+                let mut pen_down = false;
+                for &(x, y) in &trace.points {
+                    if !(x.is_finite() && y.is_finite()) {
+                        pen_down = false;
+                        continue;
+                    }
+                    if pen_down {
+                        ctx.line_to(px(x), py(y));
+                    } else {
+                        ctx.move_to(px(x), py(y));
+                        pen_down = true;
+                    }
+                }
+                ctx.stroke()?;
+            }
+            PlotKind::Scatter => {
+                for &(x, y) in trace
+                    .points
+                    .iter()
+                    .filter(|(x, y)| x.is_finite() && y.is_finite())
+                {
+                    ctx.new_sub_path();
+                    ctx.arc(px(x), py(y), POINT_RADIUS, 0.0, 2.0 * std::f64::consts::PI);
+                }
+                ctx.fill()?;
+            }
+            PlotKind::Histogram => {
+                for &(x, y) in &trace.points {
+                    ctx.rectangle(
+                        px(x),
+                        py(y),
+                        px(x + plot.bin_width) - px(x),
+                        py(0.0) - py(y),
+                    );
+                }
+                ctx.fill_preserve()?;
+                ctx.set_source_rgb(1.0, 1.0, 1.0);
+                ctx.stroke()?;
+            }
+        }
+    }
+    ctx.restore()?;
+    if plot.traces.len() > 1 {
+        draw_legend(plot, ctx, MARGIN_LEFT + pw, MARGIN_TOP)?;
+    }
+    Ok(())
+}
+
+fn draw_legend(plot: &Plot, ctx: &Context, right: f64, top: f64) -> cairo::Result<()> {
+    ctx.set_font_size(11.0);
+    let width = plot
+        .traces
+        .iter()
+        .filter_map(|t| ctx.text_extents(&t.label).ok())
+        .map(|e| e.width())
+        .fold(0.0, f64::max)
+        + 30.0;
+    let height = plot.traces.len() as f64 * 15.0 + 6.0;
+    let left = right - width - 8.0;
+    let top = top + 8.0;
+    ctx.set_source_rgba(1.0, 1.0, 1.0, 0.8);
+    ctx.rectangle(left, top, width, height);
+    ctx.fill_preserve()?;
+    ctx.set_source_rgb(0.6, 0.6, 0.6);
+    ctx.set_line_width(1.0);
+    ctx.stroke()?;
+    for (i, trace) in plot.traces.iter().enumerate() {
+        let y = top + 12.0 + i as f64 * 15.0;
+        let (r, g, b) = PALETTE[i % PALETTE.len()];
+        ctx.set_source_rgb(r, g, b);
+        ctx.rectangle(left + 5.0, y - 7.0, 15.0, 6.0);
+        ctx.fill()?;
+        ctx.set_source_rgb(0.0, 0.0, 0.0);
+        ctx.move_to(left + 25.0, y);
+        ctx.show_text(&trace.label)?;
+    }
+    Ok(())
+}
+
+/// Node of the point drawn closest to (x, y) on a plot of size (w, h)
+pub fn node_at(plot: &Plot, w: i32, h: i32, x: f64, y: f64) -> Option<usize> {
+    let frame = Frame::new(plot, w as f64, h as f64)?;
+    plot.traces
+        .iter()
+        .flat_map(|t| t.points.iter().zip(&t.nodes))
+        .map(|(&(px, py), &n)| ((frame.px(px) - x).hypot(frame.py(py) - y), n))
+        .filter(|(d, _)| *d <= POINT_RADIUS * 2.0)
+        .min_by(|a, b| a.0.total_cmp(&b.0))
+        .map(|(_, n)| n)
+}
+
+/// Save the plot to a svg, pdf or png file based on the extension
+pub fn export_plot(plot: &Plot, name: &str, w: i32, h: i32) -> anyhow::Result<()> {
+    let ext: &str = name.split('.').last().unwrap_or_default();
+    match ext {
+        "svg" => {
+            let mut svg = cairo::SvgSurface::new(w as f64, h as f64, Some(name))?;
+            let ctx = cairo::Context::new(&mut svg)?;
+            draw_plot(plot, &ctx, w, h)?;
+        }
+        "pdf" => {
+            let mut pdf = cairo::PdfSurface::new(w as f64, h as f64, name)?;
+            let ctx = cairo::Context::new(&mut pdf)?;
+            draw_plot(plot, &ctx, w, h)?;
+        }
+        "png" => {
+            let mut png = cairo::ImageSurface::create(cairo::Format::ARgb32, w * 4, h * 4)?;
+            let ctx = cairo::Context::new(&mut png)?;
+            ctx.scale(4.0, 4.0);
+            draw_plot(plot, &ctx, w, h)?;
+            drop(ctx);
+            let mut f = std::fs::File::create(name)?;
+            png.write_to_png(&mut f)?;
+        }
+        e => anyhow::bail!("Unsupported export format {e:?}, use svg, pdf or png"),
+    }
+    Ok(())
+}
+
+fn bounds<'a>(points: impl Iterator<Item = &'a (f64, f64)>) -> Option<((f64, f64), (f64, f64))> {
+    let mut b: Option<((f64, f64), (f64, f64))> = None;
+    for &(x, y) in points.filter(|(x, y)| x.is_finite() && y.is_finite()) {
+        b = Some(match b {
+            Some(((x0, x1), (y0, y1))) => ((x0.min(x), x1.max(x)), (y0.min(y), y1.max(y))),
+            None => ((x, x), (y, y)),
+        });
+    }
+    b
+}
+
+/// Range covered by the ticks and the data
+fn span(ticks: &[f64], min: f64, max: f64) -> (f64, f64) {
+    let lo = ticks.first().copied().unwrap_or(min).min(min);
+    let hi = ticks.last().copied().unwrap_or(max).max(max);
+    if hi > lo {
+        (lo, hi)
+    } else {
+        (lo - 1.0, hi + 1.0)
+    }
+}
+
+fn step(ticks: &[f64]) -> f64 {
+    match ticks {
+        [a, b, ..] => b - a,
+        _ => 1.0,
+    }
+}
+
+/// Round step (1, 2 or 5 times a power of 10) just above `raw`
+fn nice_step(raw: f64) -> f64 {
+    let mag = 10f64.powf(raw.log10().floor());
+    [1.0, 2.0, 5.0, 10.0]
+        .into_iter()
+        .map(|m| m * mag)
+        .find(|s| *s >= raw)
+        .unwrap_or(10.0 * mag)
+}
+
+fn nice_ticks(min: f64, max: f64) -> Vec<f64> {
+    let (min, max) = if max > min {
+        (min, max)
+    } else {
+        (min - 1.0, max + 1.0)
+    };
+    let step = nice_step((max - min) / TICKS as f64);
+    let start = (min / step).floor() as i64;
+    let end = (max / step).ceil() as i64;
+    (start..=end).map(|i| i as f64 * step).collect()
+}
+
+/// Ticks at round time intervals, the values are in seconds
+fn time_ticks(min: f64, max: f64) -> Vec<f64> {
+    const STEPS: [f64; 16] = [
+        1.0, 5.0, 15.0, 30.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 21600.0, 43200.0, 86400.0,
+        172800.0, 604800.0, 2592000.0, 7776000.0,
+    ];
+    const YEAR: f64 = 31536000.0;
+    let raw = (max - min) / TICKS as f64;
+    let Some(step) = STEPS.into_iter().find(|s| *s >= raw) else {
+        // years don't have a fixed length, so use the new year days
+        let years = nice_step(raw / YEAR).max(1.0) as i64;
+        let (y0, _, _) = civil_from_days((min / 86400.0).floor() as i64);
+        let (y1, _, _) = civil_from_days((max / 86400.0).ceil() as i64);
+        return (y0.div_euclid(years)..=(y1.div_euclid(years) + 1))
+            .map(|k| days_from_civil(k * years, 1, 1) as f64 * 86400.0)
+            .collect();
+    };
+    let start = (min / step).floor() as i64;
+    let end = (max / step).ceil() as i64;
+    (start..=end).map(|i| i as f64 * step).collect()
+}
+
+fn format_number(v: f64, step: f64) -> String {
+    let decimals = (-step.abs().log10().floor()).max(0.0) as usize;
+    format!("{v:.decimals$}")
+}
+
+fn format_time(t: f64, step: f64) -> String {
+    let secs = t.round() as i64;
+    let days = secs.div_euclid(86400);
+    let rem = secs.rem_euclid(86400);
+    let (y, m, d) = civil_from_days(days);
+    if step >= 31536000.0 {
+        format!("{y}")
+    } else if step >= 86400.0 {
+        format!("{y}-{m:02}-{d:02}")
+    } else if step >= 60.0 {
+        format!("{m:02}-{d:02} {:02}:{:02}", rem / 3600, rem % 3600 / 60)
+    } else {
+        format!("{:02}:{:02}:{:02}", rem / 3600, rem % 3600 / 60, rem % 60)
+    }
+}
+
+/// Parse `YYYY-MM-DD[ HH:MM[:SS]]` into seconds since the unix epoch
+fn parse_time(txt: &str) -> f64 {
+    let txt = txt.trim();
+    let (date, time) = match txt.split_once(['T', ' ']) {
+        Some((d, t)) => (d, Some(t)),
+        None => (txt, None),
+    };
+    let mut dparts = date.splitn(3, '-').map(|p| p.parse::<i64>());
+    let (Some(Ok(y)), Some(Ok(m)), Some(Ok(d))) = (dparts.next(), dparts.next(), dparts.next())
+    else {
+        return f64::NAN;
+    };
+    let mut secs = days_from_civil(y, m, d) as f64 * 86400.0;
+    if let Some(time) = time {
+        // ignore the timezone offset if any
+        let time = time.split(['+', '-', 'Z']).next().unwrap_or_default();
+        for (part, mult) in time.split(':').zip([3600.0, 60.0, 1.0]) {
+            secs += part.parse::<f64>().unwrap_or_default() * mult;
+        }
+    }
+    secs
+}
+
+/// Days since 1970-01-01 of the (proleptic gregorian) date
+fn days_from_civil(y: i64, m: i64, d: i64) -> i64 {
+    let y = if m <= 2 { y - 1 } else { y };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (m + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + d - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+/// Date (year, month, day) from the days since 1970-01-01
+fn civil_from_days(z: i64) -> (i64, i64, i64) {
+    let z = z + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let d = doy - (153 * mp + 2) / 5 + 1;
+    let m = if mp < 10 { mp + 3 } else { mp - 9 };
+    (
+        if m <= 2 {
+            yoe + era * 400 + 1
+        } else {
+            yoe + era * 400
+        },
+        m,
+        d,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() <= 1e-9 * b.abs().max(1.0), "{a} != {b}");
+    }
+
+    #[test]
+    fn civil_days() {
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(days_from_civil(1969, 12, 31), -1);
+        assert_eq!(days_from_civil(2000, 2, 29), 11016);
+        assert_eq!(days_from_civil(2000, 3, 1), 11017);
+        assert_eq!(days_from_civil(1900, 1, 1), -25567);
+        assert_eq!(civil_from_days(11016), (2000, 2, 29));
+        assert_eq!(civil_from_days(-1), (1969, 12, 31));
+        for z in (-800_000..800_000).step_by(997) {
+            let (y, m, d) = civil_from_days(z);
+            assert_eq!(days_from_civil(y, m, d), z);
+        }
+    }
+
+    #[test]
+    fn times() {
+        let day = days_from_civil(2020, 1, 2) as f64 * 86400.0;
+        let t = day + 3.0 * 3600.0 + 4.0 * 60.0 + 5.0;
+        assert_close(parse_time("2020-01-02"), day);
+        assert_close(parse_time("2020-01-02 03:04:05"), t);
+        assert_close(parse_time("2020-01-02T03:04:05"), t);
+        assert_close(parse_time("2020-01-02T03:04:05Z"), t);
+        assert_close(parse_time("2020-01-02T03:04:05+05:30"), t);
+        assert_close(parse_time("2020-01-02T03:04:05-05:00"), t);
+        assert_close(parse_time("1969-12-31"), -86400.0);
+        assert!(parse_time("2020-01").is_nan());
+    }
+
+    #[test]
+    fn steps_and_ticks() {
+        assert_close(nice_step(0.3), 0.5);
+        assert_close(nice_step(1.0), 1.0);
+        assert_close(nice_step(0.012), 0.02);
+        assert_close(nice_step(7.0), 10.0);
+        assert_close(nice_step(1234.0), 2000.0);
+        let ticks = nice_ticks(0.0, 10.0);
+        assert_eq!(ticks.len(), 6);
+        assert_close(ticks[1], 2.0);
+        assert_close(ticks[5], 10.0);
+        let ticks = nice_ticks(3.0, 3.0);
+        assert!(ticks[0] <= 3.0 && 3.0 <= ticks[ticks.len() - 1]);
+        assert_close(step(&ticks), 0.5);
+    }
+
+    #[test]
+    fn time_steps() {
+        let ticks = time_ticks(0.0, 6.0 * 86400.0);
+        assert_eq!(ticks.len(), 7);
+        assert_close(step(&ticks), 86400.0);
+        let min = days_from_civil(2000, 1, 1) as f64 * 86400.0;
+        let max = days_from_civil(2010, 6, 1) as f64 * 86400.0;
+        let ticks = time_ticks(min, max);
+        assert_close(ticks[0], min);
+        assert!(*ticks.last().unwrap() >= max);
+        for t in ticks {
+            let (y, m, d) = civil_from_days((t / 86400.0) as i64);
+            assert_eq!((y % 2, m, d), (0, 1, 1));
+        }
+    }
+
+    #[test]
+    fn histogram_constant_values() {
+        for v in [1e12, -3.5, 0.0, 1e-300] {
+            let (start, width, counts) = histogram_bins(&[v; 5]).unwrap();
+            assert_eq!(counts.iter().sum::<usize>(), 5);
+            assert!(counts.len() <= 2);
+            assert!(start <= v && v < start + width * counts.len() as f64);
+        }
+        let (_, width, counts) = histogram_bins(&[0.0, 1.0, 2.0, 3.0]).unwrap();
+        assert_close(width, 2.0);
+        assert_eq!(counts, vec![2, 2]);
+        assert!(histogram_bins(&[]).is_none());
+    }
+
+    #[test]
+    fn time_labels() {
+        let t = days_from_civil(2021, 3, 4) as f64 * 86400.0 + 5.0 * 3600.0 + 6.0 * 60.0 + 7.0;
+        assert_eq!(format_time(t, 31536000.0), "2021");
+        assert_eq!(format_time(t, 86400.0), "2021-03-04");
+        assert_eq!(format_time(t, 3600.0), "03-04 05:06");
+        assert_eq!(format_time(t, 1.0), "05:06:07");
+        assert_eq!(format_time(-1.0, 1.0), "23:59:59");
+    }
+}
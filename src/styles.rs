@@ -0,0 +1,430 @@
+use super::colors::{AttrColor, Color};
+use nadi_core::attrs::{AttrMap, Attribute, FromAttribute};
+
+/// keys in the `drawtable` columns that are only used by the gui
+const STYLE_KEYS: [&str; 14] = [
+    "color",
+    "bgcolor",
+    "bold",
+    "decimals",
+    "thousands",
+    "scientific",
+    "rules",
+    "when",
+    "max_width",
+    "overflow",
+    "group",
+    "sparkline",
+    "sparkline_type",
+    "minmax",
+];
+
+/// Style of a table cell
+#[derive(Debug, Clone, Default)]
+pub struct CellStyle {
+    pub color: Option<Color>,
+    pub bgcolor: Option<Color>,
+    pub bold: bool,
+}
+
+impl CellStyle {
+    fn from_map(map: &AttrMap) -> Self {
+        Self {
+            color: map_color(map, "color"),
+            bgcolor: map_color(map, "bgcolor"),
+            bold: map_value::<bool>(map, "bold").unwrap_or_default(),
+        }
+    }
+
+    /// Overwrite the style with the values set in the `other`
+    fn update(&mut self, other: &CellStyle) {
+        if other.color.is_some() {
+            self.color = other.color.clone();
+        }
+        if other.bgcolor.is_some() {
+            self.bgcolor = other.bgcolor.clone();
+        }
+        self.bold |= other.bold;
+    }
+}
+
+/// Formatting of the numeric values in a column
+#[derive(Debug, Clone, Default)]
+pub struct NumberFormat {
+    pub decimals: Option<usize>,
+    pub thousands: bool,
+    pub scientific: bool,
+}
+
+impl NumberFormat {
+    fn from_map(map: &AttrMap) -> Self {
+        Self {
+            decimals: map_value::<i64>(map, "decimals").map(|d| d.max(0) as usize),
+            thousands: map_value::<bool>(map, "thousands").unwrap_or_default(),
+            scientific: map_value::<bool>(map, "scientific").unwrap_or_default(),
+        }
+    }
+
+    fn is_default(&self) -> bool {
+        self.decimals.is_none() && !self.thousands && !self.scientific
+    }
+
+    /// Format the cell if it is a number, or return it as it is
+    pub fn format(&self, cell: &str) -> String {
+        if self.is_default() {
+            return cell.to_string();
+        }
+        let Ok(val) = cell.trim().parse::<f64>() else {
+            return cell.to_string();
+        };
+        if self.scientific {
+            return match self.decimals {
+                Some(d) => format!("{val:.d$e}"),
+                None => format!("{val:e}"),
+            };
+        }
+        let txt = match self.decimals {
+            Some(d) => format!("{val:.d$}"),
+            None => val.to_string(),
+        };
+        if self.thousands {
+            thousands_sep(&txt)
+        } else {
+            txt
+        }
+    }
+}
+
+/// Comparison used in the conditional styling rules
+#[derive(Debug, Clone, Copy)]
+enum RuleOp {
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Eq,
+    Ne,
+}
+
+/// Conditional style like `{when="< 0", color="red"}`
+#[derive(Debug, Clone)]
+pub struct StyleRule {
+    op: RuleOp,
+    value: String,
+    style: CellStyle,
+}
+
+impl StyleRule {
+    fn from_attr(attr: &Attribute) -> Option<Self> {
+        let Attribute::Table(map) = attr else {
+            return None;
+        };
+        let cond = map_value::<String>(map, "when")?;
+        let cond = cond.trim();
+        let (op, value) = [
+            ("<=", RuleOp::Le),
+            (">=", RuleOp::Ge),
+            ("==", RuleOp::Eq),
+            ("!=", RuleOp::Ne),
+            ("<", RuleOp::Lt),
+            (">", RuleOp::Gt),
+        ]
+        .into_iter()
+        .find_map(|(s, op)| cond.strip_prefix(s).map(|v| (op, v.trim())))?;
+        Some(Self {
+            op,
+            value: value.trim_matches('"').to_string(),
+            style: CellStyle::from_map(map),
+        })
+    }
+
+    fn matches(&self, cell: &str) -> bool {
+        let cell = cell.trim();
+        let ord = match (cell.parse::<f64>(), self.value.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.partial_cmp(&b),
+            _ => Some(cell.cmp(self.value.as_str())),
+        };
+        let Some(ord) = ord else {
+            return false;
+        };
+        match self.op {
+            RuleOp::Lt => ord.is_lt(),
+            RuleOp::Le => ord.is_le(),
+            RuleOp::Gt => ord.is_gt(),
+            RuleOp::Ge => ord.is_ge(),
+            RuleOp::Eq => ord.is_eq(),
+            RuleOp::Ne => ord.is_ne(),
+        }
+    }
+}
+
+/// What to do with the cells wider than the `max_width`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum Overflow {
+    #[default]
+    Wrap,
+    Ellipsis,
+}
+
+/// How the values of the series are drawn in a sparkline
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum SparkType {
+    #[default]
+    Line,
+    Bar,
+}
+
+/// Column showing a node series as a small chart like
+/// `{header="Flow", sparkline="streamflow", minmax=true}`
+#[derive(Debug, Clone)]
+pub struct Sparkline {
+    pub series: String,
+    pub ty: SparkType,
+    /// show the range of the values next to the chart
+    pub minmax: bool,
+}
+
+impl Sparkline {
+    fn from_map(map: &AttrMap) -> Option<Self> {
+        let series = map_value::<String>(map, "sparkline")?;
+        let ty = match map_value::<String>(map, "sparkline_type").as_deref() {
+            Some("bar") => SparkType::Bar,
+            Some("line") | None => SparkType::Line,
+            Some(t) => {
+                eprintln!("Invalid sparkline_type {t:?}, should be line or bar");
+                SparkType::Line
+            }
+        };
+        Some(Self {
+            series,
+            ty,
+            minmax: map_value::<bool>(map, "minmax").unwrap_or_default(),
+        })
+    }
+}
+
+/// Gui specific settings of a `drawtable` column
+#[derive(Debug, Clone, Default)]
+pub struct ColumnStyle {
+    pub style: CellStyle,
+    pub format: NumberFormat,
+    pub rules: Vec<StyleRule>,
+    pub max_width: Option<f64>,
+    pub overflow: Overflow,
+    /// header spanning the consecutive columns with the same group
+    pub group: Option<String>,
+    pub sparkline: Option<Sparkline>,
+}
+
+impl ColumnStyle {
+    fn from_map(map: &AttrMap) -> Self {
+        let rules = match map.get("rules") {
+            Some(Attribute::Array(rules)) => {
+                rules.iter().filter_map(StyleRule::from_attr).collect()
+            }
+            Some(rule) => StyleRule::from_attr(rule).into_iter().collect(),
+            None => vec![],
+        };
+        let overflow = match map_value::<String>(map, "overflow").as_deref() {
+            Some("ellipsis") => Overflow::Ellipsis,
+            Some("wrap") | None => Overflow::Wrap,
+            Some(o) => {
+                eprintln!("Invalid overflow {o:?}, should be wrap or ellipsis");
+                Overflow::Wrap
+            }
+        };
+        Self {
+            style: CellStyle::from_map(map),
+            format: NumberFormat::from_map(map),
+            rules,
+            max_width: map_value::<f64>(map, "max_width")
+                .or_else(|| map_value::<i64>(map, "max_width").map(|w| w as f64)),
+            overflow,
+            group: map_value::<String>(map, "group"),
+            sparkline: Sparkline::from_map(map),
+        }
+    }
+
+    /// Style of the cell with the given (unformatted) value
+    pub fn cell_style(&self, cell: &str) -> CellStyle {
+        let mut style = self.style.clone();
+        for r in self.rules.iter().filter(|r| r.matches(cell)) {
+            style.update(&r.style);
+        }
+        style
+    }
+}
+
+/// Separate the gui specific column settings from the `drawtable`
+/// attribute, so the rest can be read as a [`nadi_core::table::Table`]
+pub fn split_styles(attr: &Attribute) -> (Attribute, Vec<ColumnStyle>) {
+    let Attribute::Array(cols) = attr else {
+        return (attr.clone(), vec![]);
+    };
+    let (cols, styles): (Vec<Attribute>, Vec<ColumnStyle>) = cols
+        .iter()
+        .map(|c| match c {
+            Attribute::Table(map) => {
+                let style = ColumnStyle::from_map(map);
+                let mut map = map.clone();
+                for k in STYLE_KEYS {
+                    map.remove(k);
+                }
+                // sparkline columns don't need a template of their own
+                if style.sparkline.is_some() && !map.contains_key("template") {
+                    map.insert("template".into(), Attribute::String("".into()));
+                }
+                (Attribute::Table(map), style)
+            }
+            c => (c.clone(), ColumnStyle::default()),
+        })
+        .unzip();
+    (Attribute::Array(cols.into()), styles)
+}
+
+fn map_value<T: FromAttribute>(map: &AttrMap, key: &str) -> Option<T> {
+    map.get(key).and_then(FromAttribute::from_attr)
+}
+
+fn map_color(map: &AttrMap, key: &str) -> Option<Color> {
+    match map_value::<AttrColor>(map, key).map(|c| c.color()) {
+        Some(Ok(c)) => Some(c),
+        Some(Err(e)) => {
+            eprintln!("{e}");
+            None
+        }
+        None => None,
+    }
+}
+
+/// Insert `,` between the thousands in the integer part
+fn thousands_sep(num: &str) -> String {
+    let (sign, num) = match num.strip_prefix('-') {
+        Some(n) => ("-", n),
+        None => ("", num),
+    };
+    let (int, frac) = match num.split_once('.') {
+        Some((i, f)) => (i, Some(f)),
+        None => (num, None),
+    };
+    let mut out = String::from(sign);
+    for (i, c) in int.chars().enumerate() {
+        if i > 0 && (int.len() - i) % 3 == 0 {
+            out.push(',');
+        }
+        out.push(c);
+    }
+    if let Some(f) = frac {
+        out.push('.');
+        out.push_str(f);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(pairs: &[(&str, Attribute)]) -> Attribute {
+        let mut map = AttrMap::new();
+        for (k, v) in pairs {
+            map.insert((*k).into(), v.clone());
+        }
+        Attribute::Table(map)
+    }
+
+    fn first_column(cols: Attribute) -> AttrMap {
+        let Attribute::Array(cols) = cols else {
+            panic!("columns should stay an array");
+        };
+        let Some(Attribute::Table(first)) = cols.first() else {
+            panic!("column should stay a table");
+        };
+        first.clone()
+    }
+
+    #[test]
+    fn style_keys_removed() {
+        let cols = Attribute::Array(
+            vec![
+                table(&[
+                    ("header", Attribute::String("Area".into())),
+                    ("template", Attribute::String("{area}".into())),
+                    ("decimals", Attribute::Integer(2)),
+                    ("bold", Attribute::Bool(true)),
+                ]),
+                table(&[("header", Attribute::String("Name".into()))]),
+            ]
+            .into(),
+        );
+        let (cols, styles) = split_styles(&cols);
+        let first = first_column(cols);
+        assert!(first.contains_key("header") && first.contains_key("template"));
+        assert!(!first.contains_key("decimals") && !first.contains_key("bold"));
+        assert_eq!(styles.len(), 2);
+        assert_eq!(styles[0].format.decimals, Some(2));
+        assert!(styles[0].style.bold);
+        assert!(!styles[1].style.bold);
+    }
+
+    #[test]
+    fn not_an_array() {
+        let (attr, styles) = split_styles(&Attribute::Integer(1));
+        assert!(matches!(attr, Attribute::Integer(1)));
+        assert!(styles.is_empty());
+    }
+
+    #[test]
+    fn number_format() {
+        let f = NumberFormat {
+            decimals: Some(1),
+            thousands: true,
+            scientific: false,
+        };
+        assert_eq!(f.format("1234567.89"), "1,234,567.9");
+        assert_eq!(f.format("-1234"), "-1,234.0");
+        assert_eq!(f.format("abc"), "abc");
+        assert_eq!(NumberFormat::default().format(" 12 "), " 12 ");
+        let f = NumberFormat {
+            thousands: true,
+            ..Default::default()
+        };
+        assert_eq!(f.format("1e10"), "10,000,000,000");
+        assert_eq!(f.format(" -1234.5 "), "-1,234.5");
+    }
+
+    #[test]
+    fn column_group() {
+        let cols = Attribute::Array(
+            vec![
+                table(&[
+                    ("header", Attribute::String("Area".into())),
+                    ("group", Attribute::String("Basin".into())),
+                ]),
+                table(&[("header", Attribute::String("Name".into()))]),
+            ]
+            .into(),
+        );
+        let (cols, styles) = split_styles(&cols);
+        assert!(!first_column(cols).contains_key("group"));
+        assert_eq!(styles[0].group.as_deref(), Some("Basin"));
+        assert!(styles[1].group.is_none());
+    }
+
+    #[test]
+    fn sparkline_gets_template() {
+        let cols = Attribute::Array(
+            vec![table(&[
+                ("header", Attribute::String("Flow".into())),
+                ("sparkline", Attribute::String("flow".into())),
+                ("sparkline_type", Attribute::String("bar".into())),
+            ])]
+            .into(),
+        );
+        let (cols, styles) = split_styles(&cols);
+        let first = first_column(cols);
+        assert!(styles[0].sparkline.is_some());
+        assert!(first.contains_key("template"));
+        assert!(!first.contains_key("sparkline") && !first.contains_key("sparkline_type"));
+    }
+}
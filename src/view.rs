@@ -0,0 +1,343 @@
+use abi_stable::std_types::{RNone, RSome};
+use nadi_core::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+const ROW_TOLERANCE: f64 = 10.0;
+pub const HANDLE_SIZE: f64 = 8.0;
+
+/// Display state of the network drawing that is not part of the
+/// network itself (search results, node positions, etc.)
+#[derive(Default)]
+pub struct NetworkView {
+    /// indices of the nodes matching the current search
+    pub matches: Vec<usize>,
+    /// position of the current match in `matches`
+    pub current: Option<usize>,
+    /// location (x, y) of the nodes in the last drawing
+    pub positions: HashMap<usize, (f64, f64)>,
+    /// highlighted nodes, everything else is dimmed
+    pub highlight: Option<Highlight>,
+    /// node the context menu was opened on
+    pub context_node: Option<usize>,
+    /// node selected in the drawing or the table
+    pub selected: Option<usize>,
+    /// names of the nodes whose upstream subtree is collapsed
+    pub collapsed: HashSet<String>,
+    /// area (x, y, width, height) of the node labels in the last drawing
+    pub label_areas: HashMap<usize, (f64, f64, f64, f64)>,
+    /// location of the collapse handles in the last drawing
+    pub handles: HashMap<usize, (f64, f64)>,
+    /// incremented every time the network or the layout changes, the
+    /// selection, highlight and search matches are drawn over it
+    pub revision: u64,
+    /// name of the figure shown, the network attributes otherwise
+    pub figure: Option<String>,
+    /// nodes passing the filter of the figure, all nodes if `None`
+    pub filter: Option<HashSet<usize>>,
+    /// nodes passing the filter from the toolbar, all nodes if `None`
+    pub user_filter: Option<HashSet<usize>>,
+}
+
+/// Recorded drawing of the network, replayed until the view changes
+pub struct RenderCache {
+    pub surface: cairo::RecordingSurface,
+    /// revision of the view and the size it was drawn for
+    pub key: (u64, i32, i32),
+}
+
+/// Thumbnail of the whole network for the overview
+pub struct Minimap {
+    pub surface: cairo::ImageSurface,
+    /// revision of the view and the size the thumbnail was drawn for
+    pub key: (u64, i32, i32),
+    pub scale: f64,
+}
+
+/// Rows of the nodes in the drawing after collapsing the subtrees
+pub struct NodeRows {
+    /// row of each node by index, `None` if it is hidden
+    pub rows: Vec<Option<usize>>,
+    /// number of hidden nodes upstream of the collapsed nodes
+    pub hidden: HashMap<usize, usize>,
+    /// number of visible nodes
+    pub count: usize,
+}
+
+impl NodeRows {
+    pub fn row(&self, ind: usize) -> Option<usize> {
+        self.rows.get(ind).copied().flatten()
+    }
+
+    pub fn hidden_count(&self, ind: usize) -> Option<usize> {
+        self.hidden.get(&ind).copied()
+    }
+
+    /// Whether the direct output of the node is hidden, so the edge
+    /// goes to a node further downstream
+    pub fn skips_output(&self, node: &NodeInner) -> bool {
+        match node.output() {
+            RSome(o) => self.row(o.lock().index()).is_none(),
+            RNone => false,
+        }
+    }
+
+    /// Nearest downstream node that is not hidden
+    pub fn visible_output(&self, node: &NodeInner) -> Option<Node> {
+        let mut out = match node.output() {
+            RSome(o) => o.clone(),
+            RNone => return None,
+        };
+        loop {
+            let next = {
+                let o = out.lock();
+                if self.row(o.index()).is_some() {
+                    break;
+                }
+                match o.output() {
+                    RSome(n) => n.clone(),
+                    RNone => return None,
+                }
+            };
+            out = next;
+        }
+        Some(out)
+    }
+}
+
+pub struct Highlight {
+    pub nodes: HashSet<usize>,
+    /// propositional selection string for the highlighted nodes
+    pub selection: String,
+}
+
+impl Highlight {
+    /// The node and all the nodes upstream of it
+    pub fn upstream(net: &Network, ind: usize) -> Option<Self> {
+        let node = net.node(ind)?;
+        let end = quote_name(node.lock().name());
+        let mut nodes = HashSet::new();
+        // the paths from the sources cover all the upstream nodes
+        let mut sources = vec![];
+        let mut stack = vec![node.clone()];
+        while let Some(n) = stack.pop() {
+            let n = n.lock();
+            nodes.insert(n.index());
+            if n.inputs().is_empty() && n.index() != ind {
+                sources.push((n.index(), quote_name(n.name())));
+            }
+            stack.extend(n.inputs().iter().cloned());
+        }
+        sources.sort_by_key(|(i, _)| std::cmp::Reverse(*i));
+        let selection = if sources.is_empty() {
+            format!("node[{end}]")
+        } else {
+            let paths: Vec<String> = sources
+                .into_iter()
+                .map(|(_, s)| format!("{s} -> {end}"))
+                .collect();
+            format!("node[{}]", paths.join(", "))
+        };
+        Some(Self { nodes, selection })
+    }
+
+    /// The path from the node to the outlet
+    pub fn downstream(net: &Network, ind: usize) -> Option<Self> {
+        let node = net.node(ind)?.clone();
+        let start = quote_name(node.lock().name());
+        let mut nodes = HashSet::new();
+        let mut end = start.clone();
+        let mut current = Some(node);
+        while let Some(n) = current {
+            let n = n.lock();
+            nodes.insert(n.index());
+            end = quote_name(n.name());
+            current = match n.output() {
+                RSome(o) => Some(o.clone()),
+                RNone => None,
+            };
+        }
+        let selection = if start == end {
+            format!("node[{start}]")
+        } else {
+            format!("node[{start} -> {end}]")
+        };
+        Some(Self { nodes, selection })
+    }
+}
+
+impl NetworkView {
+    /// View for the exported figures, without the search results or
+    /// highlights
+    pub fn for_export(&self) -> Self {
+        Self {
+            collapsed: self.collapsed.clone(),
+            figure: self.figure.clone(),
+            filter: self.filter.clone(),
+            user_filter: self.user_filter.clone(),
+            ..Default::default()
+        }
+    }
+
+    pub fn rows(&self, net: &Network) -> NodeRows {
+        let mut hidden_nodes: Vec<bool> = (0..net.nodes_count())
+            .map(|i| !self.is_visible(i))
+            .collect();
+        let mut hidden = HashMap::new();
+        for name in &self.collapsed {
+            let Some(node) = net.node_by_name(name) else {
+                continue;
+            };
+            let node = node.lock();
+            let mut stack: Vec<Node> = node.inputs().iter().cloned().collect();
+            let mut count = 0;
+            while let Some(n) = stack.pop() {
+                let n = n.lock();
+                if !hidden_nodes[n.index()] {
+                    hidden_nodes[n.index()] = true;
+                    count += 1;
+                }
+                stack.extend(n.inputs().iter().cloned());
+            }
+            hidden.insert(node.index(), count);
+        }
+        let mut count = 0;
+        let rows: Vec<Option<usize>> = hidden_nodes
+            .into_iter()
+            .map(|h| {
+                if h {
+                    None
+                } else {
+                    count += 1;
+                    Some(count - 1)
+                }
+            })
+            .collect();
+        // collapsed nodes inside other collapsed subtrees are hidden
+        hidden.retain(|i, _| rows[*i].is_some());
+        NodeRows {
+            rows,
+            hidden,
+            count,
+        }
+    }
+
+    /// Whether the node passes both the figure and the toolbar filters
+    pub fn is_visible(&self, ind: usize) -> bool {
+        [&self.filter, &self.user_filter]
+            .into_iter()
+            .all(|f| f.as_ref().map(|f| f.contains(&ind)).unwrap_or(true))
+    }
+
+    pub fn toggle_collapse(&mut self, name: &str) {
+        if !self.collapsed.remove(name) {
+            self.collapsed.insert(name.to_string());
+        }
+        self.invalidate();
+    }
+
+    /// Forget the node indices, they belong to the network that was
+    /// replaced; the collapsed nodes are kept as they are by name
+    pub fn reset_nodes(&mut self) {
+        self.matches.clear();
+        self.current = None;
+        self.positions.clear();
+        self.label_areas.clear();
+        self.highlight = None;
+        self.context_node = None;
+        self.selected = None;
+        self.handles.clear();
+        self.filter = None;
+        self.user_filter = None;
+        self.invalidate();
+    }
+
+    /// Mark the drawings depending on the network as outdated
+    pub fn invalidate(&mut self) {
+        self.revision += 1;
+    }
+
+    /// Node whose collapse handle is at the given point
+    pub fn handle_at(&self, x: f64, y: f64) -> Option<usize> {
+        self.handles
+            .iter()
+            .find(|(_, (hx, hy))| (hx - x).abs() <= HANDLE_SIZE && (hy - y).abs() <= HANDLE_SIZE)
+            .map(|(i, _)| *i)
+    }
+
+    pub fn is_dimmed(&self, ind: usize) -> bool {
+        self.highlight
+            .as_ref()
+            .map(|h| !h.nodes.contains(&ind))
+            .unwrap_or(false)
+    }
+
+    /// Node drawn closest to the given point in the last drawing
+    pub fn node_at(&self, x: f64, y: f64) -> Option<usize> {
+        self.positions
+            .iter()
+            .filter(|(_, (_, ny))| (ny - y).abs() < ROW_TOLERANCE)
+            .min_by(|(_, (ax, ay)), (_, (bx, by))| {
+                let da = (ay - y).abs() * 100.0 + (ax - x).abs();
+                let db = (by - y).abs() * 100.0 + (bx - x).abs();
+                da.total_cmp(&db)
+            })
+            .map(|(i, _)| *i)
+    }
+
+    pub fn current_match(&self) -> Option<usize> {
+        self.current.and_then(|c| self.matches.get(c).copied())
+    }
+
+    pub fn search(&mut self, net: &Network, pattern: &str) {
+        self.matches.clear();
+        self.current = None;
+        if pattern.is_empty() {
+            return;
+        }
+        for n in net.nodes() {
+            let n = n.lock();
+            let label = crate::network::get_node_label(&n);
+            if fuzzy_match(pattern, n.name()) || fuzzy_match(pattern, &label) {
+                self.matches.push(n.index());
+            }
+        }
+        if !self.matches.is_empty() {
+            self.current = Some(0);
+        }
+    }
+
+    pub fn next_match(&mut self, forward: bool) -> Option<usize> {
+        let total = self.matches.len();
+        if total == 0 {
+            return None;
+        }
+        self.current = Some(match self.current {
+            Some(c) if forward => (c + 1) % total,
+            Some(c) => (c + total - 1) % total,
+            None => 0,
+        });
+        self.current_match()
+    }
+}
+
+/// Quote the node name if it can't be used as is in the tasks
+pub fn quote_name(name: &str) -> String {
+    if !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_alphanumeric() || c == '_')
+    {
+        name.to_string()
+    } else {
+        format!("{name:?}")
+    }
+}
+
+/// Case insensitive subsequence match of `pattern` in `text`
+pub fn fuzzy_match(pattern: &str, text: &str) -> bool {
+    let mut text = text.chars().flat_map(char::to_lowercase);
+    pattern
+        .chars()
+        .flat_map(char::to_lowercase)
+        .filter(|c| !c.is_whitespace())
+        .all(|p| text.any(|t| t == p))
+}
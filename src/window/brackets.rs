@@ -0,0 +1,355 @@
+use super::{in_bulk_edit, task_bounds, Window};
+use glib::clone;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gdk, glib, TextBuffer, TextIter, TextMark};
+
+/// Characters that are closed automatically when typed
+const PAIRS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('"', '"')];
+
+/// Brackets in the text outside of the strings and comments
+struct Brackets {
+    /// char offsets of the brackets and their match, `None` if unmatched
+    pairs: Vec<(usize, Option<usize>)>,
+    /// brackets still open at the end of the text
+    open: Vec<usize>,
+    /// whether the text ends inside a string or a comment
+    in_literal: bool,
+}
+
+impl Window {
+    pub(super) fn setup_brackets(&self) {
+        // added after the completion, which gets the keys first
+        let keys = gtk::EventControllerKey::new();
+        keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+        keys.connect_key_pressed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |_, key, _, state| window.bracket_key(key, state)
+        ));
+        self.imp().tv_frame.add_controller(keys);
+    }
+
+    pub(super) fn setup_buffer_brackets(&self, buf: &TextBuffer) {
+        buf.connect_cursor_position_notify(|tb| {
+            if !in_bulk_edit(tb) {
+                highlight_brackets(tb);
+            }
+        });
+        buf.connect_changed(|tb| {
+            if !in_bulk_edit(tb) {
+                highlight_brackets(tb);
+            }
+        });
+    }
+
+    fn bracket_key(&self, key: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
+        if state.intersects(gdk::ModifierType::CONTROL_MASK | gdk::ModifierType::ALT_MASK) {
+            return glib::Propagation::Proceed;
+        }
+        let tv = &self.imp().tv_frame;
+        let buf = tv.buffer();
+        let handled = match key {
+            gdk::Key::Return | gdk::Key::KP_Enter => smart_newline(&buf),
+            gdk::Key::BackSpace => delete_pair(&buf),
+            _ => key.to_unicode().is_some_and(|c| type_bracket(&buf, c)),
+        };
+        if handled {
+            tv.scroll_mark_onscreen(&buf.get_insert());
+            glib::Propagation::Stop
+        } else {
+            glib::Propagation::Proceed
+        }
+    }
+}
+
+fn scan_brackets(text: &str) -> Brackets {
+    let mut pairs = vec![];
+    let mut stack: Vec<(usize, char)> = vec![];
+    let (mut in_string, mut in_comment, mut escaped) = (false, false, false);
+    for (i, c) in text.chars().enumerate() {
+        if in_comment {
+            in_comment = c != '\n';
+            continue;
+        }
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => (),
+            }
+            continue;
+        }
+        match c {
+            '#' => in_comment = true,
+            '"' => in_string = true,
+            '(' | '[' | '{' => stack.push((i, c)),
+            ')' | ']' | '}' => match stack.last() {
+                Some(&(o, oc)) if PAIRS.contains(&(oc, c)) => {
+                    stack.pop();
+                    pairs.push((o, Some(i)));
+                    pairs.push((i, Some(o)));
+                }
+                _ => pairs.push((i, None)),
+            },
+            _ => (),
+        }
+    }
+    pairs.extend(stack.iter().map(|(o, _)| (*o, None)));
+    Brackets {
+        pairs,
+        open: stack.into_iter().map(|(o, _)| o).collect(),
+        in_literal: in_string || in_comment,
+    }
+}
+
+/// Brackets in the buffer between the iters, the offsets are from the
+/// start of the buffer
+fn buffer_brackets(buf: &TextBuffer, start: &TextIter, end: &TextIter) -> Brackets {
+    let base = start.offset() as usize;
+    let mut brackets = scan_brackets(&buf.text(start, end, true));
+    for (o, m) in brackets.pairs.iter_mut() {
+        *o += base;
+        if let Some(m) = m {
+            *m += base;
+        }
+    }
+    for o in brackets.open.iter_mut() {
+        *o += base;
+    }
+    brackets
+}
+
+/// Start and end of the task the cursor is in
+fn cursor_task(buf: &TextBuffer) -> (TextIter, TextIter) {
+    let line = buf.iter_at_mark(&buf.get_insert()).line();
+    task_bounds(buf, line, line)
+}
+
+/// Brackets from the start of the task till the cursor
+fn brackets_before_cursor(buf: &TextBuffer) -> Brackets {
+    let ins = buf.iter_at_mark(&buf.get_insert());
+    buffer_brackets(buf, &cursor_task(buf).0, &ins)
+}
+
+/// Highlight the bracket at the cursor and its match inside the task, or
+/// show it as an error if it doesn't have one
+fn highlight_brackets(buf: &TextBuffer) {
+    // only the brackets highlighted before need to be cleared
+    for mark in unsafe { buf.steal_data::<Vec<TextMark>>("bracket_marks") }.unwrap_or_default() {
+        let s = buf.iter_at_mark(&mark);
+        let mut e = s;
+        e.forward_char();
+        buf.remove_tag_by_name("bracket_match", &s, &e);
+        buf.remove_tag_by_name("bracket_error", &s, &e);
+        buf.delete_mark(&mark);
+    }
+    let cursor = buf.iter_at_mark(&buf.get_insert()).offset() as usize;
+    let (start, end) = cursor_task(buf);
+    let brackets = buffer_brackets(buf, &start, &end);
+    let at = |pos: usize| brackets.pairs.iter().find(|(o, _)| *o == pos);
+    let found = at(cursor).or_else(|| cursor.checked_sub(1).and_then(at));
+    let mut marks = vec![];
+    let mut tag_char = |name: &str, offset: usize| {
+        let s = buf.iter_at_offset(offset as i32);
+        let mut e = s;
+        e.forward_char();
+        buf.apply_tag_by_name(name, &s, &e);
+        marks.push(buf.create_mark(None, &s, true));
+    };
+    match found {
+        Some(&(o, Some(m))) => {
+            tag_char("bracket_match", o);
+            tag_char("bracket_match", m);
+        }
+        Some(&(o, None)) => tag_char("bracket_error", o),
+        None => (),
+    }
+    unsafe { buf.set_data("bracket_marks", marks) };
+}
+
+/// Whitespace at the start of the line
+fn line_indent(buf: &TextBuffer, line: i32) -> String {
+    let Some(start) = buf.iter_at_line(line) else {
+        return String::new();
+    };
+    let mut end = start;
+    if !end.ends_line() {
+        end.forward_to_line_end();
+    }
+    buf.text(&start, &end, true)
+        .chars()
+        .take_while(|c| *c == ' ' || *c == '\t')
+        .collect()
+}
+
+/// Close the opening brackets and quotes, or step over the closing ones
+/// that were inserted before
+fn type_bracket(buf: &TextBuffer, c: char) -> bool {
+    let ins = buf.iter_at_mark(&buf.get_insert());
+    let next = ins.char();
+    let selection = buf.selection_bounds();
+    if selection.is_none() && next == c && matches!(c, ')' | ']' | '}' | '"') {
+        let mut after = ins;
+        after.forward_char();
+        buf.place_cursor(&after);
+        return true;
+    }
+    if matches!(c, ')' | ']' | '}') {
+        return dedent_closing(buf, c);
+    }
+    let Some(&(_, close)) = PAIRS.iter().find(|(o, _)| *o == c) else {
+        return false;
+    };
+    if brackets_before_cursor(buf).in_literal {
+        return false;
+    }
+    if let Some((s, mut e)) = selection {
+        // surround the selection
+        let s = s.offset();
+        buf.begin_user_action();
+        buf.insert(&mut e, &close.to_string());
+        buf.insert(&mut buf.iter_at_offset(s), &c.to_string());
+        buf.end_user_action();
+        return true;
+    }
+    let mut prev = ins;
+    let prev = if prev.backward_char() {
+        prev.char()
+    } else {
+        ' '
+    };
+    // don't close the brackets typed before a word, or the quotes after one
+    if !(ins.is_end() || next.is_whitespace() || matches!(next, ')' | ']' | '}' | ','))
+        || (c == '"' && (prev.is_alphanumeric() || prev == '"'))
+    {
+        return false;
+    }
+    buf.begin_user_action();
+    buf.insert_at_cursor(&format!("{c}{close}"));
+    let mut cursor = buf.iter_at_mark(&buf.get_insert());
+    cursor.backward_char();
+    buf.place_cursor(&cursor);
+    buf.end_user_action();
+    true
+}
+
+/// Align the closing bracket typed on an empty line with the line of the
+/// opening one
+fn dedent_closing(buf: &TextBuffer, c: char) -> bool {
+    let ins = buf.iter_at_mark(&buf.get_insert());
+    let Some(mut line_start) = buf.iter_at_line(ins.line()) else {
+        return false;
+    };
+    let before = buf.text(&line_start, &ins, true);
+    if before.is_empty() || !before.chars().all(char::is_whitespace) {
+        return false;
+    }
+    let Some(&open) = brackets_before_cursor(buf).open.last() else {
+        return false;
+    };
+    let indent = line_indent(buf, buf.iter_at_offset(open as i32).line());
+    buf.begin_user_action();
+    buf.delete(&mut line_start, &mut buf.iter_at_mark(&buf.get_insert()));
+    buf.insert(&mut line_start, &format!("{indent}{c}"));
+    buf.end_user_action();
+    true
+}
+
+/// Indent the new line one level more than the line with the open
+/// bracket, or the same as the current line
+fn smart_newline(buf: &TextBuffer) -> bool {
+    if buf.has_selection() {
+        return false;
+    }
+    let ins = buf.iter_at_mark(&buf.get_insert());
+    let brackets = brackets_before_cursor(buf);
+    if brackets.in_literal {
+        return false;
+    }
+    let Some(&open) = brackets.open.last() else {
+        let indent = line_indent(buf, ins.line());
+        let indent: String = indent.chars().take(ins.line_offset() as usize).collect();
+        if indent.is_empty() {
+            return false;
+        }
+        buf.insert_at_cursor(&format!("\n{indent}"));
+        return true;
+    };
+    let base = line_indent(buf, buf.iter_at_offset(open as i32).line());
+    let closing = matches!(ins.char(), ')' | ']' | '}');
+    buf.begin_user_action();
+    buf.insert_at_cursor(&format!("\n{base}\t"));
+    if closing {
+        // put the closing bracket on its own line
+        let cursor = buf.iter_at_mark(&buf.get_insert()).offset();
+        buf.insert_at_cursor(&format!("\n{base}"));
+        buf.place_cursor(&buf.iter_at_offset(cursor));
+    }
+    buf.end_user_action();
+    true
+}
+
+/// Delete both the brackets when deleting an empty pair
+fn delete_pair(buf: &TextBuffer) -> bool {
+    if buf.has_selection() {
+        return false;
+    }
+    let ins = buf.iter_at_mark(&buf.get_insert());
+    let mut prev = ins;
+    if !prev.backward_char() || !PAIRS.contains(&(prev.char(), ins.char())) {
+        return false;
+    }
+    let mut next = ins;
+    next.forward_char();
+    buf.begin_user_action();
+    buf.delete(&mut prev, &mut next);
+    buf.end_user_action();
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matched(text: &str, pos: usize) -> Option<Option<usize>> {
+        scan_brackets(text)
+            .pairs
+            .iter()
+            .find(|(o, _)| *o == pos)
+            .map(|(_, m)| *m)
+    }
+
+    #[test]
+    fn nested_pairs() {
+        let text = "f(a[1], {b})";
+        assert_eq!(matched(text, 1), Some(Some(11)));
+        assert_eq!(matched(text, 11), Some(Some(1)));
+        assert_eq!(matched(text, 3), Some(Some(5)));
+        assert_eq!(matched(text, 8), Some(Some(10)));
+        assert!(scan_brackets(text).open.is_empty());
+    }
+
+    #[test]
+    fn unmatched() {
+        let b = scan_brackets("f(a]");
+        assert_eq!(b.open, vec![1]);
+        assert!(b.pairs.contains(&(3, None)));
+        assert!(b.pairs.contains(&(1, None)));
+    }
+
+    #[test]
+    fn strings_and_comments() {
+        let text = "f(\"(\\\"\") # )\n";
+        assert_eq!(matched(text, 1), Some(Some(7)));
+        let b = scan_brackets(text);
+        assert!(b.open.is_empty());
+        assert!(!b.in_literal);
+        assert!(scan_brackets("x = \"(").in_literal);
+        assert!(scan_brackets("# (").in_literal);
+        assert!(!scan_brackets("# (\n").in_literal);
+    }
+}
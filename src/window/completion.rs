@@ -0,0 +1,405 @@
+use super::{in_bulk_edit, Window};
+use glib::clone;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gdk, glib};
+use itertools::Itertools;
+use nadi_core::attrs::HasAttributes;
+use nadi_core::functions::FuncArg;
+use nadi_core::tasks::TaskContext;
+use std::collections::BTreeSet;
+
+/// Maximum number of candidates shown in the popup
+const MAX_CANDIDATES: usize = 50;
+/// Minimum length of the word before the popup is shown while typing
+const MIN_PREFIX: usize = 2;
+/// Keywords at the start of the task lines
+const KEYWORDS: [&str; 3] = ["node", "network", "env"];
+
+/// Completion candidate in the editor
+#[derive(Debug, Clone)]
+struct Candidate {
+    text: String,
+    kind: &'static str,
+    info: String,
+}
+
+/// State of the editor completion popup
+struct Completer {
+    popover: gtk::Popover,
+    list: gtk::ListBox,
+    /// buffer offset where the completed word starts
+    start: i32,
+    candidates: Vec<Candidate>,
+}
+
+impl Window {
+    pub(super) fn setup_completion(&self) {
+        let tv = &self.imp().tv_frame;
+        let list = gtk::ListBox::new();
+        list.set_selection_mode(gtk::SelectionMode::Browse);
+        list.connect_row_activated(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_, row| window.accept_completion(row.index())
+        ));
+        let scroll = gtk::ScrolledWindow::builder()
+            .child(&list)
+            .hscrollbar_policy(gtk::PolicyType::Never)
+            .propagate_natural_height(true)
+            .max_content_height(250)
+            .min_content_width(300)
+            .build();
+        let popover = gtk::Popover::builder()
+            .child(&scroll)
+            .autohide(false)
+            .has_arrow(false)
+            .position(gtk::PositionType::Bottom)
+            .can_focus(false)
+            .build();
+        popover.set_parent(tv);
+        unsafe {
+            tv.set_data(
+                "completer",
+                Completer {
+                    popover,
+                    list,
+                    start: 0,
+                    candidates: vec![],
+                },
+            );
+        }
+
+        // capture phase so the keys reach us before the text view
+        let keys = gtk::EventControllerKey::new();
+        keys.set_propagation_phase(gtk::PropagationPhase::Capture);
+        keys.connect_key_pressed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |_, key, _, state| window.completion_key(key, state)
+        ));
+        tv.add_controller(keys);
+
+        let focus = gtk::EventControllerFocus::new();
+        focus.connect_leave(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.hide_completion()
+        ));
+        tv.add_controller(focus);
+    }
+
+    pub(super) fn setup_buffer_completion(&self, buf: &gtk::TextBuffer) {
+        buf.connect_changed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |tb| {
+                // only complete while the user is typing, not when a file is loaded
+                if window.imp().tv_frame.has_focus() && !in_bulk_edit(tb) {
+                    window.update_completion(false);
+                }
+            }
+        ));
+    }
+
+    fn completer(&self) -> Option<&mut Completer> {
+        unsafe {
+            self.imp()
+                .tv_frame
+                .data::<Completer>("completer")
+                .map(|c| &mut *c.as_ptr())
+        }
+    }
+
+    fn completion_key(&self, key: gdk::Key, state: gdk::ModifierType) -> glib::Propagation {
+        if key == gdk::Key::space && state.contains(gdk::ModifierType::CONTROL_MASK) {
+            self.update_completion(true);
+            return glib::Propagation::Stop;
+        }
+        let Some(comp) = self.completer() else {
+            return glib::Propagation::Proceed;
+        };
+        if !comp.popover.is_visible() {
+            return glib::Propagation::Proceed;
+        }
+        let selected = comp.list.selected_row().map(|r| r.index()).unwrap_or(0);
+        let count = comp.candidates.len() as i32;
+        let select = |ind: i32| {
+            if let Some(row) = comp.list.row_at_index(ind.rem_euclid(count.max(1))) {
+                comp.list.select_row(Some(&row));
+            }
+        };
+        match key {
+            gdk::Key::Down => select(selected + 1),
+            gdk::Key::Up => select(selected - 1),
+            gdk::Key::Return | gdk::Key::KP_Enter | gdk::Key::Tab => {
+                self.accept_completion(selected)
+            }
+            gdk::Key::Escape => self.hide_completion(),
+            _ => return glib::Propagation::Proceed,
+        }
+        glib::Propagation::Stop
+    }
+
+    pub(super) fn hide_completion(&self) {
+        if let Some(comp) = self.completer() {
+            comp.popover.popdown();
+        }
+    }
+
+    /// Show the candidates for the word at the cursor, `force` shows
+    /// them even if the word is short (Ctrl+Space)
+    fn update_completion(&self, force: bool) {
+        let tv = &self.imp().tv_frame;
+        let buf = tv.buffer();
+        let cursor = buf.iter_at_mark(&buf.get_insert());
+        let line_start = buf
+            .iter_at_line(cursor.line())
+            .expect("should be valid line");
+        let before = buf.text(&line_start, &cursor, false);
+        let word_len = before
+            .chars()
+            .rev()
+            .take_while(|c| c.is_alphanumeric() || *c == '_')
+            .count();
+        let Some(comp) = self.completer() else {
+            return;
+        };
+        let visible = comp.popover.is_visible();
+        if !force && !visible && word_len < MIN_PREFIX {
+            return;
+        }
+        let head: String = before
+            .chars()
+            .take(before.chars().count() - word_len)
+            .collect();
+        let word: String = before.chars().skip(head.chars().count()).collect();
+        let candidates = self.completion_candidates(&head, &word);
+        if candidates.is_empty() || (!force && candidates.iter().all(|c| c.text == word)) {
+            comp.popover.popdown();
+            return;
+        }
+
+        while let Some(row) = comp.list.first_child() {
+            comp.list.remove(&row);
+        }
+        for c in &candidates {
+            let label = gtk::Label::new(None);
+            label.set_xalign(0.0);
+            label.set_markup(&format!(
+                "<b>{}</b> <span foreground=\"gray\" size=\"small\">{} {}</span>",
+                glib::markup_escape_text(&c.text),
+                c.kind,
+                glib::markup_escape_text(&c.info)
+            ));
+            comp.list.append(&label);
+        }
+        comp.list.select_row(comp.list.row_at_index(0).as_ref());
+        comp.start = cursor.offset() - word_len as i32;
+        comp.candidates = candidates;
+
+        let loc = tv.iter_location(&cursor);
+        let (x, y) = tv.buffer_to_window_coords(
+            gtk::TextWindowType::Widget,
+            loc.x(),
+            loc.y() + loc.height(),
+        );
+        comp.popover
+            .set_pointing_to(Some(&gdk::Rectangle::new(x, y, 1, 1)));
+        if !visible {
+            comp.popover.popup();
+        }
+    }
+
+    /// Replace the word at the cursor with the candidate
+    fn accept_completion(&self, ind: i32) {
+        let Some(comp) = self.completer() else {
+            return;
+        };
+        let Some(cand) = comp.candidates.get(ind as usize).cloned() else {
+            return;
+        };
+        comp.popover.popdown();
+        let buf = self.imp().tv_frame.buffer();
+        let mut start = buf.iter_at_offset(comp.start);
+        let mut end = buf.iter_at_mark(&buf.get_insert());
+        buf.begin_user_action();
+        buf.delete(&mut start, &mut end);
+        buf.insert(&mut start, &cand.text);
+        buf.end_user_action();
+        buf.place_cursor(&start);
+        // inserting the text shows the popup again
+        comp.popover.popdown();
+    }
+
+    /// Candidates for the `word` following the `head` of the line
+    fn completion_candidates(&self, head: &str, word: &str) -> Vec<Candidate> {
+        let Some(tctx) = (unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") }) else {
+            return vec![];
+        };
+        let tctx: &TaskContext = unsafe { tctx.as_ref() };
+        let trimmed = head.trim_start();
+        let keyword = KEYWORDS
+            .into_iter()
+            .find(|k| trimmed.starts_with(k))
+            .unwrap_or("env");
+        let rest = trimmed.strip_prefix(keyword).unwrap_or(trimmed).trim();
+
+        let mut cands = vec![];
+        if let Some(func) = open_function(head) {
+            // inside the function call: argument names and attributes
+            if let Some(args) = function_args(tctx, keyword, &func) {
+                cands.extend(args.into_iter().map(|a| Candidate {
+                    text: format!("{}=", a.name),
+                    kind: "arg",
+                    info: a.ty.to_string(),
+                }));
+            }
+            cands.extend(attribute_candidates(tctx, keyword));
+        } else if trimmed.is_empty() {
+            cands.extend(KEYWORDS.into_iter().map(|k| Candidate {
+                text: k.to_string(),
+                kind: "keyword",
+                info: String::new(),
+            }));
+            cands.extend(function_candidates(tctx, "env"));
+        } else if head.ends_with('.') {
+            cands.extend(attribute_candidates(tctx, keyword));
+        } else if head.contains('=') {
+            cands.extend(function_candidates(tctx, "env"));
+            cands.extend(attribute_candidates(tctx, keyword));
+        } else if rest.is_empty() || rest.ends_with(']') || rest.ends_with(')') {
+            cands.extend(function_candidates(tctx, keyword));
+        } else {
+            cands.extend(attribute_candidates(tctx, keyword));
+        }
+
+        // prefix matches first, then the ones containing the word
+        let word_lc = word.to_lowercase();
+        let (mut pre, rest): (Vec<Candidate>, Vec<Candidate>) = cands
+            .into_iter()
+            .filter(|c| c.text.to_lowercase().contains(&word_lc))
+            .unique_by(|c| c.text.clone())
+            .partition(|c| c.text.starts_with(word));
+        pre.extend(rest);
+        pre.truncate(MAX_CANDIDATES);
+        pre
+    }
+}
+
+/// Name of the function whose parenthesis is open before the cursor
+fn open_function(head: &str) -> Option<String> {
+    let mut depth = 0;
+    let mut in_str = false;
+    let mut open = None;
+    for (i, c) in head.char_indices() {
+        match c {
+            '"' => in_str = !in_str,
+            '(' if !in_str => {
+                depth += 1;
+                open = Some(i);
+            }
+            ')' if !in_str => depth -= 1,
+            _ => (),
+        }
+    }
+    if depth <= 0 {
+        return None;
+    }
+    let name: String = head[..open?]
+        .chars()
+        .rev()
+        .take_while(|c| c.is_alphanumeric() || *c == '_' || *c == '.')
+        .collect::<Vec<char>>()
+        .into_iter()
+        .rev()
+        .collect();
+    Some(name).filter(|n| !n.is_empty())
+}
+
+/// Functions that can be called with the keyword, node and network
+/// tasks can also call the env functions
+fn function_candidates(tctx: &TaskContext, keyword: &str) -> Vec<Candidate> {
+    let funcs = &tctx.functions;
+    let names: Vec<(&'static str, String)> = match keyword {
+        "node" => funcs
+            .node_functions()
+            .keys()
+            .map(|k| ("node", k.to_string()))
+            .chain(funcs.env_functions().keys().map(|k| ("env", k.to_string())))
+            .collect(),
+        "network" => funcs
+            .network_functions()
+            .keys()
+            .map(|k| ("network", k.to_string()))
+            .chain(funcs.env_functions().keys().map(|k| ("env", k.to_string())))
+            .collect(),
+        _ => funcs
+            .env_functions()
+            .keys()
+            .map(|k| ("env", k.to_string()))
+            .collect(),
+    };
+    names
+        .into_iter()
+        .sorted()
+        .map(|(kind, full)| {
+            let (plugin, name) = full.split_once('.').unwrap_or(("", &full));
+            Candidate {
+                text: name.to_string(),
+                kind,
+                info: plugin.to_string(),
+            }
+        })
+        .collect()
+}
+
+fn function_args(tctx: &TaskContext, keyword: &str, name: &str) -> Option<Vec<FuncArg>> {
+    let funcs = &tctx.functions;
+    let env = || funcs.env(name).map(|f| f.args().into_iter().collect());
+    match keyword {
+        "node" => funcs
+            .node(name)
+            .map(|f| f.args().into_iter().collect())
+            .or_else(env),
+        "network" => funcs
+            .network(name)
+            .map(|f| f.args().into_iter().collect())
+            .or_else(env),
+        _ => env(),
+    }
+}
+
+/// Attributes present on the nodes, or on the network for network tasks
+fn attribute_candidates(tctx: &TaskContext, keyword: &str) -> Vec<Candidate> {
+    let net = &tctx.network;
+    let (kind, names): (&'static str, BTreeSet<String>) = if keyword == "network" {
+        (
+            "network attr",
+            net.attr_map().keys().map(|k| k.to_string()).collect(),
+        )
+    } else {
+        (
+            "node attr",
+            net.nodes()
+                .flat_map(|n| {
+                    n.lock()
+                        .attr_map()
+                        .keys()
+                        .map(|k| k.to_string())
+                        .collect::<Vec<_>>()
+                })
+                .collect(),
+        )
+    };
+    names
+        .into_iter()
+        .map(|text| Candidate {
+            text,
+            kind,
+            info: String::new(),
+        })
+        .collect()
+}
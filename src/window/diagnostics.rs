@@ -0,0 +1,370 @@
+use super::{in_bulk_edit, is_task_start, task_bounds, Window};
+use crate::lint::{lint, Assigned};
+use glib::clone;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{glib, TextIter};
+use nadi_core::parser::tokenizer::{self, TaskToken};
+use nadi_core::parser::{ParseError, ParseErrorType};
+use nadi_core::tasks::TaskContext;
+use std::time::Duration;
+
+/// Time after the last edit to check the tasks
+const CHECK_DELAY: Duration = Duration::from_millis(300);
+
+/// Source of the diagnostic
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DiagnosticKind {
+    Parse,
+    /// from the static checks of the parsed tasks
+    Lint,
+    /// from running the tasks
+    Runtime,
+}
+
+impl DiagnosticKind {
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Parse => "<span foreground=\"red\">ParseError</span>",
+            Self::Lint => "<span foreground=\"orange\">Warning</span>",
+            Self::Runtime => "<span foreground=\"red\">Error</span>",
+        }
+    }
+
+    fn tag(&self) -> &'static str {
+        match self {
+            Self::Lint => "warning",
+            _ => "error",
+        }
+    }
+}
+
+/// Error shown in the editor, the marks move with the edits
+struct Diagnostic {
+    start: gtk::TextMark,
+    end: gtk::TextMark,
+    message: String,
+    kind: DiagnosticKind,
+}
+
+impl Window {
+    pub(super) fn setup_diagnostics(&self) {
+        let imp = self.imp();
+        imp.tv_frame.set_has_tooltip(true);
+        imp.tv_frame.connect_query_tooltip(clone!(
+            #[weak(rename_to=window)]
+            self,
+            #[upgrade_or]
+            false,
+            move |tv, x, y, _, tooltip| {
+                let (bx, by) = tv.window_to_buffer_coords(gtk::TextWindowType::Widget, x, y);
+                let Some(iter) = tv.iter_at_location(bx, by) else {
+                    return false;
+                };
+                match window.diagnostic_at(&iter) {
+                    Some(msg) => {
+                        tooltip.set_text(Some(&msg));
+                        true
+                    }
+                    None => false,
+                }
+            }
+        ));
+        imp.lb_problems.connect_row_activated(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_, row| window.goto_problem(row.index())
+        ));
+    }
+
+    /// Diagnostics are kept in each buffer as the marks belong to it
+    pub(super) fn setup_buffer_diagnostics(&self, buf: &gtk::TextBuffer) {
+        unsafe {
+            buf.set_data("diagnostics", Vec::<Diagnostic>::new());
+        }
+        buf.connect_changed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |tb| {
+                if !in_bulk_edit(tb) {
+                    window.schedule_check();
+                }
+            }
+        ));
+    }
+
+    fn diagnostics(&self) -> Option<&mut Vec<Diagnostic>> {
+        unsafe {
+            self.imp()
+                .tv_frame
+                .buffer()
+                .data::<Vec<Diagnostic>>("diagnostics")
+                .map(|d| &mut *d.as_ptr())
+        }
+    }
+
+    /// Check the tasks once the typing pauses, parsing the whole buffer
+    /// on each key press makes the typing lag
+    fn schedule_check(&self) {
+        let tv = &self.imp().tv_frame;
+        if let Some(source) = unsafe { tv.steal_data::<glib::SourceId>("check_source") } {
+            source.remove();
+        }
+        let source = glib::timeout_add_local_once(
+            CHECK_DELAY,
+            clone!(
+                #[weak(rename_to=window)]
+                self,
+                move || {
+                    unsafe {
+                        window
+                            .imp()
+                            .tv_frame
+                            .steal_data::<glib::SourceId>("check_source");
+                    }
+                    window.check_changed_tasks();
+                    window.refresh_outline();
+                }
+            ),
+        );
+        unsafe { tv.set_data("check_source", source) };
+    }
+
+    /// Parse and check the whole buffer again
+    pub(super) fn check_tasks(&self) {
+        let buf = self.imp().tv_frame.buffer();
+        unsafe { buf.set_data("check_lines", (0, i32::MAX)) };
+        self.check_changed_tasks();
+    }
+
+    /// Parse and check the tasks changed since the last check to update
+    /// the parse errors and the warnings
+    fn check_changed_tasks(&self) {
+        let buf = self.imp().tv_frame.buffer();
+        let (first, last) = unsafe { buf.data::<(i32, i32)>("check_lines") }
+            .map(|d| *unsafe { d.as_ref() })
+            .unwrap_or((0, i32::MAX));
+        unsafe { buf.set_data("check_lines", (i32::MAX, -1)) };
+        if last < first {
+            return;
+        }
+        let (mut start, mut end) = task_bounds(&buf, first, last);
+        // the attributes are checked against the tasks of the whole file,
+        // and a change in the tasks setting them can affect all the others
+        let assigned = tokenizer::get_tokens(&buf.text(&buf.start_iter(), &buf.end_iter(), true))
+            .map(|t| Assigned::new(&t))
+            .unwrap_or_default();
+        let same = unsafe { buf.steal_data::<Assigned>("assigned") }
+            .is_some_and(|a| a.same_tasks(&assigned));
+        if !same {
+            (start, end) = buf.bounds();
+        }
+        let mut tokens = tokenizer::get_tokens(&buf.text(&start, &end, true));
+        // an unclosed string continues till the end of the buffer
+        let unclosed = match &tokens {
+            Ok(t) => t.iter().any(|t| t.ty == TaskToken::Quote),
+            Err(_) => true,
+        };
+        if unclosed && !end.is_end() {
+            end = buf.end_iter();
+            tokens = tokenizer::get_tokens(&buf.text(&start, &end, true));
+        }
+        let mut warnings = vec![];
+        let err = match tokens {
+            Ok(tokens) => match nadi_core::parser::tasks::parse(tokens.clone()) {
+                Ok(_) => {
+                    if let Some(tctx) =
+                        unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") }
+                    {
+                        let tctx: &TaskContext = unsafe { tctx.as_ref() };
+                        let base = start.offset() as usize;
+                        warnings = lint(&tokens, &tctx.functions, &tctx.network, &assigned, base);
+                    }
+                    None
+                }
+                Err(e) => Some(e),
+            },
+            Err(e) => Some(e),
+        };
+        unsafe { buf.set_data("assigned", assigned) };
+        let Some(diags) = self.diagnostics() else {
+            return;
+        };
+        // the diagnostics outside the checked tasks are kept
+        diags.retain(|d| {
+            let pos = buf.iter_at_mark(&d.start);
+            if d.kind == DiagnosticKind::Runtime || pos < start || (pos >= end && !end.is_end()) {
+                return true;
+            }
+            buf.delete_mark(&d.start);
+            buf.delete_mark(&d.end);
+            false
+        });
+        if let Some(e) = err {
+            diags.push(parse_diagnostic(&buf, &e, start.line()));
+        }
+        let base = start.offset() as usize;
+        for w in warnings {
+            let s = buf.iter_at_offset((base + w.offset) as i32);
+            let e = buf.iter_at_offset((base + w.offset + w.len) as i32);
+            diags.push(Diagnostic {
+                start: buf.create_mark(None, &s, true),
+                end: buf.create_mark(None, &e, false),
+                message: w.message,
+                kind: DiagnosticKind::Lint,
+            });
+        }
+        self.refresh_diagnostics();
+    }
+
+    /// Mark the error from running the task starting at `line`
+    pub(super) fn add_runtime_error(&self, line: i32, msg: &str) {
+        let buf = self.imp().tv_frame.buffer();
+        let Some(mut start) = buf.iter_at_line(line) else {
+            return;
+        };
+        while start.char().is_whitespace() && !start.ends_line() {
+            start.forward_char();
+        }
+        let mut end = start;
+        if !end.ends_line() {
+            end.forward_to_line_end();
+        }
+        if let Some(diags) = self.diagnostics() {
+            diags.push(Diagnostic {
+                start: buf.create_mark(None, &start, true),
+                end: buf.create_mark(None, &end, false),
+                message: msg.trim().to_string(),
+                kind: DiagnosticKind::Runtime,
+            });
+        }
+        self.refresh_diagnostics();
+    }
+
+    pub(super) fn clear_runtime_errors(&self) {
+        let buf = self.imp().tv_frame.buffer();
+        if let Some(diags) = self.diagnostics() {
+            diags.retain(|d| {
+                let runtime = d.kind == DiagnosticKind::Runtime;
+                if runtime {
+                    buf.delete_mark(&d.start);
+                    buf.delete_mark(&d.end);
+                }
+                !runtime
+            });
+        }
+        self.refresh_diagnostics();
+    }
+
+    /// Underline the errors and list them in the problems list
+    fn refresh_diagnostics(&self) {
+        let imp = self.imp();
+        let buf = imp.tv_frame.buffer();
+        buf.remove_tag_by_name("error", &buf.start_iter(), &buf.end_iter());
+        buf.remove_tag_by_name("warning", &buf.start_iter(), &buf.end_iter());
+        let list = &imp.lb_problems;
+        while let Some(row) = list.first_child() {
+            list.remove(&row);
+        }
+        let Some(diags) = self.diagnostics() else {
+            return;
+        };
+        diags.sort_by_key(|d| buf.iter_at_mark(&d.start).offset());
+        for d in diags.iter() {
+            let (start, end) = diagnostic_range(&buf, d);
+            buf.apply_tag_by_name(d.kind.tag(), &start, &end);
+            let label = gtk::Label::new(None);
+            label.set_xalign(0.0);
+            label.set_markup(&format!(
+                "<span foreground=\"gray\">{}:{}</span> {} {}",
+                start.line() + 1,
+                start.line_offset() + 1,
+                d.kind.label(),
+                glib::markup_escape_text(&d.message)
+            ));
+            list.append(&label);
+        }
+        imp.exp_problems
+            .set_label(Some(&format!("Problems ({})", diags.len())));
+    }
+
+    fn diagnostic_at(&self, iter: &TextIter) -> Option<String> {
+        let buf = self.imp().tv_frame.buffer();
+        let msgs: Vec<&str> = self
+            .diagnostics()?
+            .iter()
+            .filter(|d| {
+                let (start, end) = diagnostic_range(&buf, d);
+                start <= *iter && *iter < end
+            })
+            .map(|d| d.message.as_str())
+            .collect();
+        Some(msgs.join("\n")).filter(|m| !m.is_empty())
+    }
+
+    fn goto_problem(&self, ind: i32) {
+        let tv = &self.imp().tv_frame;
+        let buf = tv.buffer();
+        let Some(d) = self.diagnostics().and_then(|d| d.get(ind as usize)) else {
+            return;
+        };
+        let start = buf.iter_at_mark(&d.start);
+        buf.place_cursor(&start);
+        tv.scroll_to_mark(&buf.get_insert(), 0.1, false, 0.0, 0.0);
+        tv.grab_focus();
+    }
+
+    /// Lines of the editor where the tasks between the iters start
+    pub(super) fn task_lines(&self, start: &TextIter, end: &TextIter) -> Vec<i32> {
+        let buf = self.imp().tv_frame.buffer();
+        // the end is at the start of the line after the tasks
+        let last = if end.starts_line() && end.line() > start.line() {
+            end.line() - 1
+        } else {
+            end.line()
+        };
+        (start.line()..=last)
+            .filter(|l| is_task_start(&buf, *l))
+            .collect()
+    }
+}
+
+fn diagnostic_range(buf: &gtk::TextBuffer, d: &Diagnostic) -> (TextIter, TextIter) {
+    let start = buf.iter_at_mark(&d.start);
+    let mut end = buf.iter_at_mark(&d.end);
+    if end <= start {
+        end = start;
+        end.forward_char();
+    }
+    (start, end)
+}
+
+/// Diagnostic underlining the word at the position of the error, the
+/// line of the error counts from `first_line`
+fn parse_diagnostic(buf: &gtk::TextBuffer, err: &ParseError, first_line: i32) -> Diagnostic {
+    let line = first_line + err.line as i32;
+    let mut start = buf
+        .iter_at_line_offset(line, err.col as i32)
+        .or_else(|| buf.iter_at_line(line))
+        .unwrap_or(buf.end_iter());
+    if start.is_end() && !start.starts_line() {
+        start.backward_char();
+    }
+    let mut end = start;
+    while end.char().is_alphanumeric() || end.char() == '_' {
+        end.forward_char();
+    }
+    if end == start {
+        end.forward_char();
+    }
+    let mut message = err.ty.message().to_string();
+    if let ParseErrorType::LogicalError(s) = &err.ty {
+        message.push_str(&format!(": {s}"));
+    }
+    Diagnostic {
+        start: buf.create_mark(None, &start, true),
+        end: buf.create_mark(None, &end, false),
+        message,
+        kind: DiagnosticKind::Parse,
+    }
+}
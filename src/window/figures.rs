@@ -0,0 +1,156 @@
+use super::Window;
+use crate::figures::{figure, figures, Figure};
+use crate::view::NetworkView;
+use glib::clone;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gio, glib};
+use nadi_core::tasks::TaskContext;
+use std::collections::HashSet;
+use std::path::Path;
+
+/// Label of the tab without any figure
+const DEFAULT_FIGURE: &str = "Network";
+
+impl Window {
+    pub(super) fn setup_figures(&self) {
+        let imp = self.imp();
+        unsafe {
+            imp.box_figures
+                .set_data("figure_names", Vec::<String>::new());
+        }
+        imp.btn_export_all.connect_clicked(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.export_all()
+        ));
+    }
+
+    fn network_figures(&self) -> Vec<Figure> {
+        match unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") } {
+            Some(c) => figures(&unsafe { c.as_ref() }.network),
+            None => vec![],
+        }
+    }
+
+    /// Update the figure tabs from the `figures` attribute of the
+    /// network, and the filter of the current figure
+    pub(super) fn refresh_figures(&self) {
+        let names: Vec<String> = self.network_figures().into_iter().map(|f| f.name).collect();
+        let bx = &self.imp().box_figures;
+        let old_names = unsafe { bx.data::<Vec<String>>("figure_names") }
+            .map(|n| unsafe { n.as_ref() }.clone())
+            .unwrap_or_default();
+        let current = self.network_view().and_then(|v| v.figure.clone());
+        let current = current.filter(|c| names.contains(c));
+        if old_names != names {
+            while let Some(child) = bx.first_child() {
+                bx.remove(&child);
+            }
+            let mut group: Option<gtk::ToggleButton> = None;
+            for name in std::iter::once(None).chain(names.iter().map(Some)) {
+                let btn = gtk::ToggleButton::with_label(name.map_or(DEFAULT_FIGURE, |n| n));
+                btn.set_group(group.as_ref());
+                btn.set_active(name == current.as_ref());
+                let name = name.cloned();
+                btn.connect_toggled(clone!(
+                    #[weak(rename_to=window)]
+                    self,
+                    move |b| {
+                        if b.is_active() {
+                            window.show_figure(name.clone());
+                        }
+                    }
+                ));
+                bx.append(&btn);
+                group.get_or_insert(btn);
+            }
+            bx.set_visible(!names.is_empty());
+            unsafe { bx.set_data("figure_names", names) };
+        }
+        // the attributes used in the filter might have changed
+        self.show_figure(current);
+    }
+
+    fn show_figure(&self, name: Option<String>) {
+        let filter = name.as_deref().and_then(|n| self.figure_filter(n));
+        let Some(view) = self.network_view() else {
+            return;
+        };
+        if view.figure == name && view.filter == filter {
+            return;
+        }
+        view.figure = name;
+        view.filter = filter;
+        view.invalidate();
+        self.imp().da_network.queue_draw();
+        self.imp().da_minimap.queue_draw();
+        self.refresh_table();
+    }
+
+    /// Nodes to show in the figure, `None` to show all of them
+    fn figure_filter(&self, name: &str) -> Option<HashSet<usize>> {
+        let tctx = unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") }?;
+        let fig = figure(&unsafe { tctx.as_ref() }.network, name)?;
+        let nodes = self.filter_nodes(&fig.filter?)?;
+        Some(nodes.into_iter().collect())
+    }
+
+    fn export_all(&self) {
+        let filters = gtk::FileFilter::new();
+        for mime in ["image/png", "image/svg", "application/pdf"] {
+            filters.add_mime_type(mime);
+        }
+        let dialog = gtk::FileDialog::builder()
+            .title("Export All Figures")
+            .default_filter(&filters)
+            .initial_file(&gio::File::for_path("figures.pdf"))
+            .accept_label("Save");
+        dialog.build().save(
+            Some(&self.clone()),
+            gio::Cancellable::NONE,
+            clone!(
+                #[weak(rename_to=window)]
+                self,
+                move |file| {
+                    if let Some(path) = file.ok().and_then(|f| f.path()) {
+                        window.export_figures(&path);
+                    }
+                }
+            ),
+        );
+    }
+
+    /// Export each figure to `<stem>-<figure>.<ext>` next to the path
+    fn export_figures(&self, path: &Path) {
+        let stem = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let ext = path
+            .extension()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or("pdf".to_string());
+        let collapsed = self
+            .network_view()
+            .map(|v| v.collapsed.clone())
+            .unwrap_or_default();
+        let figs = self.network_figures();
+        if figs.is_empty() {
+            self.feed_term_text("No figures defined in the network.figures attribute\n");
+            return;
+        }
+        for fig in figs {
+            let file = path.with_file_name(format!("{stem}-{}.{ext}", fig.name));
+            let view = NetworkView {
+                collapsed: collapsed.clone(),
+                filter: self.figure_filter(&fig.name),
+                figure: Some(fig.name),
+                ..Default::default()
+            };
+            if self.export_view(file.to_string_lossy().to_string(), view) {
+                self.feed_term_text(&format!("Exported {}\n", file.display()));
+            }
+        }
+    }
+}
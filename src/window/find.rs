@@ -0,0 +1,495 @@
+use super::{bulk_edit, in_bulk_edit, Window};
+use glib::clone;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gdk, glib, TextBuffer};
+use nadi_core::parser::tokenizer::{self, TaskToken};
+use regex::RegexBuilder;
+
+/// Token types the search can be restricted to, in the order of
+/// `dd_find_tokens`
+const TOKEN_MODES: [&str; 6] = [
+    "All Text",
+    "Variables",
+    "Functions",
+    "Strings",
+    "Comments",
+    "Keywords",
+];
+
+/// Match in the buffer with character offsets
+#[derive(Debug, Clone)]
+struct FindMatch {
+    start: i32,
+    end: i32,
+    replacement: String,
+}
+
+/// Search options from the find bar
+struct FindOptions<'a> {
+    pattern: &'a str,
+    replace: &'a str,
+    regex: bool,
+    case: bool,
+    /// index into `TOKEN_MODES`
+    tokens: u32,
+    /// character range to search in
+    range: Option<(i32, i32)>,
+}
+
+impl Window {
+    pub(super) fn setup_find(&self) {
+        let imp = self.imp();
+        imp.sb_find.connect_entry(&*imp.txt_find);
+        imp.dd_find_tokens
+            .set_model(Some(&gtk::StringList::new(&TOKEN_MODES)));
+        let keys = gtk::EventControllerKey::new();
+        keys.connect_key_pressed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |_, key, _, state| {
+                if key == gdk::Key::f && state.contains(gdk::ModifierType::CONTROL_MASK) {
+                    window.show_find();
+                    return glib::Propagation::Stop;
+                }
+                glib::Propagation::Proceed
+            }
+        ));
+        imp.tv_frame.add_controller(keys);
+
+        imp.txt_find.connect_search_changed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.refresh_find()
+        ));
+        imp.txt_find.connect_activate(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.find_next(true)
+        ));
+        imp.txt_find.connect_next_match(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.find_next(true)
+        ));
+        imp.txt_find.connect_previous_match(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.find_next(false)
+        ));
+        imp.txt_find.connect_stop_search(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.imp().tv_frame.grab_focus()
+        ));
+        imp.sb_find.connect_search_mode_enabled_notify(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.refresh_find()
+        ));
+        for tb in [&imp.tb_find_regex, &imp.tb_find_case] {
+            tb.connect_toggled(clone!(
+                #[weak(rename_to=window)]
+                self,
+                move |_| window.refresh_find()
+            ));
+        }
+        imp.tb_find_selection.connect_toggled(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |tb| {
+                window.set_find_range(tb.is_active());
+                window.refresh_find();
+            }
+        ));
+        imp.dd_find_tokens.connect_selected_notify(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.refresh_find()
+        ));
+        imp.btn_find_prev.connect_clicked(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.find_next(false)
+        ));
+        imp.btn_find_next.connect_clicked(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.find_next(true)
+        ));
+        imp.btn_replace.connect_clicked(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.replace_current()
+        ));
+        imp.btn_replace_all.connect_clicked(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.replace_all()
+        ));
+        imp.txt_replace.connect_activate(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.replace_current()
+        ));
+    }
+
+    pub(super) fn setup_buffer_find(&self, buf: &TextBuffer) {
+        buf.connect_changed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |tb| {
+                if window.imp().sb_find.is_search_mode() && !in_bulk_edit(tb) {
+                    window.refresh_find();
+                }
+            }
+        ));
+    }
+
+    fn show_find(&self) {
+        let imp = self.imp();
+        let buf = imp.tv_frame.buffer();
+        // search for the selected text if it is on a single line
+        if let Some((s, e)) = buf.selection_bounds() {
+            let sel = buf.text(&s, &e, false);
+            if !sel.contains('\n') && !imp.tb_find_selection.is_active() {
+                imp.txt_find.set_text(&sel);
+            }
+        }
+        imp.sb_find.set_search_mode(true);
+        imp.txt_find.grab_focus();
+    }
+
+    /// Remember the selection to search in, as marks so the range
+    /// follows the replacements
+    fn set_find_range(&self, active: bool) {
+        let buf = self.imp().tv_frame.buffer();
+        for name in ["find_start", "find_end"] {
+            if let Some(m) = buf.mark(name) {
+                buf.delete_mark(&m);
+            }
+        }
+        if !active {
+            return;
+        }
+        let (s, e) = buf
+            .selection_bounds()
+            .unwrap_or((buf.start_iter(), buf.end_iter()));
+        buf.create_mark(Some("find_start"), &s, true);
+        buf.create_mark(Some("find_end"), &e, false);
+    }
+
+    fn find_options_matches(&self) -> Result<Vec<FindMatch>, String> {
+        let imp = self.imp();
+        let buf = imp.tv_frame.buffer();
+        let pattern = imp.txt_find.text();
+        let replace = imp.txt_replace.text();
+        let range = match (buf.mark("find_start"), buf.mark("find_end")) {
+            (Some(s), Some(e)) if imp.tb_find_selection.is_active() => {
+                Some((buf.iter_at_mark(&s).offset(), buf.iter_at_mark(&e).offset()))
+            }
+            _ => None,
+        };
+        let opts = FindOptions {
+            pattern: &pattern,
+            replace: &replace,
+            regex: imp.tb_find_regex.is_active(),
+            case: imp.tb_find_case.is_active(),
+            tokens: imp.dd_find_tokens.selected(),
+            range,
+        };
+        let text = buf.text(&buf.start_iter(), &buf.end_iter(), true);
+        find_matches(&text, &opts)
+    }
+
+    /// Highlight the matches in the current buffer
+    pub(super) fn refresh_find(&self) {
+        let imp = self.imp();
+        let buf = imp.tv_frame.buffer();
+        for tag in ["find_match", "find_current"] {
+            buf.remove_tag_by_name(tag, &buf.start_iter(), &buf.end_iter());
+        }
+        if !imp.sb_find.is_search_mode() || imp.txt_find.text().is_empty() {
+            imp.lab_find.set_text("");
+            return;
+        }
+        match self.find_options_matches() {
+            Ok(matches) => {
+                for m in &matches {
+                    buf.apply_tag_by_name(
+                        "find_match",
+                        &buf.iter_at_offset(m.start),
+                        &buf.iter_at_offset(m.end),
+                    );
+                }
+                self.update_find_label(&matches);
+            }
+            Err(e) => {
+                imp.lab_find.set_text("Error");
+                imp.lab_find.set_tooltip_text(Some(&e));
+            }
+        }
+    }
+
+    fn update_find_label(&self, matches: &[FindMatch]) {
+        let lab = &self.imp().lab_find;
+        lab.set_tooltip_text(None);
+        match self.selected_match(matches) {
+            Some(i) => lab.set_text(&format!("{} of {}", i + 1, matches.len())),
+            None => lab.set_text(&format!("{} matches", matches.len())),
+        }
+    }
+
+    /// Index of the match that is selected in the buffer
+    fn selected_match(&self, matches: &[FindMatch]) -> Option<usize> {
+        let buf = self.imp().tv_frame.buffer();
+        let (s, e) = buf.selection_bounds()?;
+        matches
+            .iter()
+            .position(|m| m.start == s.offset() && m.end == e.offset())
+    }
+
+    fn find_next(&self, forward: bool) {
+        let Ok(matches) = self.find_options_matches() else {
+            return;
+        };
+        if matches.is_empty() {
+            return;
+        }
+        let buf = self.imp().tv_frame.buffer();
+        let cursor = buf.iter_at_mark(&buf.get_insert()).offset();
+        let next = match (self.selected_match(&matches), forward) {
+            (Some(i), true) => (i + 1) % matches.len(),
+            (Some(i), false) => (i + matches.len() - 1) % matches.len(),
+            (None, true) => matches.iter().position(|m| m.start >= cursor).unwrap_or(0),
+            (None, false) => matches
+                .iter()
+                .rposition(|m| m.end <= cursor)
+                .unwrap_or(matches.len() - 1),
+        };
+        self.select_match(&matches, next);
+    }
+
+    fn select_match(&self, matches: &[FindMatch], ind: usize) {
+        let tv = &self.imp().tv_frame;
+        let buf = tv.buffer();
+        let m = &matches[ind];
+        let start = buf.iter_at_offset(m.start);
+        let end = buf.iter_at_offset(m.end);
+        buf.remove_tag_by_name("find_current", &buf.start_iter(), &buf.end_iter());
+        buf.apply_tag_by_name("find_current", &start, &end);
+        // the selection is shown as the cursor moves to the end
+        buf.select_range(&end, &start);
+        tv.scroll_to_mark(&buf.get_insert(), 0.1, false, 0.0, 0.0);
+        self.update_find_label(matches);
+    }
+
+    /// Replace the selected match and go to the next one
+    fn replace_current(&self) {
+        let Ok(matches) = self.find_options_matches() else {
+            return;
+        };
+        let Some(i) = self.selected_match(&matches) else {
+            self.find_next(true);
+            return;
+        };
+        let buf = self.imp().tv_frame.buffer();
+        let m = &matches[i];
+        let mut start = buf.iter_at_offset(m.start);
+        let mut end = buf.iter_at_offset(m.end);
+        buf.begin_user_action();
+        buf.delete(&mut start, &mut end);
+        buf.insert(&mut start, &m.replacement);
+        buf.end_user_action();
+        buf.place_cursor(&start);
+        self.find_next(true);
+    }
+
+    /// Replace all the matches, only the ones in the selection if the
+    /// search is limited to it
+    fn replace_all(&self) {
+        let matches = match self.find_options_matches() {
+            Ok(m) => m,
+            Err(e) => {
+                self.feed_term_text(&format!("{e}\n"));
+                return;
+            }
+        };
+        let buf = self.imp().tv_frame.buffer();
+        bulk_edit(&buf, || {
+            // from the end so the offsets of the rest stay valid
+            for m in matches.iter().rev() {
+                let mut start = buf.iter_at_offset(m.start);
+                let mut end = buf.iter_at_offset(m.end);
+                buf.delete(&mut start, &mut end);
+                buf.insert(&mut start, &m.replacement);
+            }
+        });
+        self.imp()
+            .lab_find
+            .set_text(&format!("Replaced {}", matches.len()));
+    }
+}
+
+/// Character ranges of the tokens of the type selected in the mode
+fn token_ranges(text: &str, mode: u32) -> Result<Vec<(i32, i32)>, String> {
+    let tokens = tokenizer::get_tokens(text).map_err(|e| e.ty.message().to_string())?;
+    let mut offset = 0;
+    let mut ranges = vec![];
+    for t in tokens {
+        let len = t.content.chars().count() as i32;
+        let keep = match (&t.ty, mode) {
+            (TaskToken::Variable, 1) => true,
+            (TaskToken::Function, 2) => true,
+            (TaskToken::String(_), 3) => true,
+            (TaskToken::Comment, 4) => true,
+            (TaskToken::Keyword(_), 5) => true,
+            _ => false,
+        };
+        if keep {
+            ranges.push((offset, offset + len));
+        }
+        offset += len;
+    }
+    Ok(ranges)
+}
+
+fn find_matches(text: &str, opts: &FindOptions) -> Result<Vec<FindMatch>, String> {
+    if opts.pattern.is_empty() {
+        return Ok(vec![]);
+    }
+    let pattern = if opts.regex {
+        opts.pattern.to_string()
+    } else {
+        regex::escape(opts.pattern)
+    };
+    let re = RegexBuilder::new(&pattern)
+        .case_insensitive(!opts.case)
+        .multi_line(true)
+        .build()
+        .map_err(|e| e.to_string())?;
+    let tokens = match opts.tokens {
+        0 => None,
+        m => Some(token_ranges(text, m)?),
+    };
+    let mut matches = vec![];
+    // byte offsets of the regex to the character offsets of the buffer
+    let (mut byte, mut chars) = (0, 0);
+    for caps in re.captures_iter(text) {
+        let m = caps.get(0).expect("whole match");
+        if m.is_empty() {
+            continue;
+        }
+        chars += text[byte..m.start()].chars().count() as i32;
+        let start = chars;
+        chars += m.as_str().chars().count() as i32;
+        byte = m.end();
+        let end = chars;
+        if let Some((s, e)) = opts.range {
+            if start < s || end > e {
+                continue;
+            }
+        }
+        if let Some(ranges) = &tokens {
+            if !ranges.iter().any(|(s, e)| *s <= start && end <= *e) {
+                continue;
+            }
+        }
+        let replacement = if opts.regex {
+            let mut rep = String::new();
+            caps.expand(opts.replace, &mut rep);
+            rep
+        } else {
+            opts.replace.to_string()
+        };
+        matches.push(FindMatch {
+            start,
+            end,
+            replacement,
+        });
+    }
+    Ok(matches)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn opts(pattern: &str) -> FindOptions {
+        FindOptions {
+            pattern,
+            replace: "",
+            regex: false,
+            case: true,
+            tokens: 0,
+            range: None,
+        }
+    }
+
+    fn ranges(text: &str, opts: &FindOptions) -> Vec<(i32, i32)> {
+        find_matches(text, opts)
+            .unwrap()
+            .into_iter()
+            .map(|m| (m.start, m.end))
+            .collect()
+    }
+
+    #[test]
+    fn plain_text() {
+        assert_eq!(ranges("a.b a+b", &opts("a.b")), vec![(0, 3)]);
+        assert!(ranges("abc", &opts("")).is_empty());
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let o = FindOptions {
+            case: false,
+            ..opts("abc")
+        };
+        assert_eq!(ranges("ABC abc", &o), vec![(0, 3), (4, 7)]);
+    }
+
+    #[test]
+    fn character_offsets() {
+        // offsets are in characters like the buffer, not bytes
+        assert_eq!(ranges("é x é x", &opts("x")), vec![(2, 3), (6, 7)]);
+    }
+
+    #[test]
+    fn regex_replacement() {
+        let o = FindOptions {
+            regex: true,
+            replace: "${2}_$1",
+            ..opts(r"(\w+)\.(\w+)")
+        };
+        let m = find_matches("node.area", &o).unwrap();
+        assert_eq!(m.len(), 1);
+        assert_eq!(m[0].replacement, "area_node");
+        assert!(find_matches("x", &opts("(")).is_ok());
+        let bad = FindOptions {
+            regex: true,
+            ..opts("(")
+        };
+        assert!(find_matches("x", &bad).is_err());
+    }
+
+    #[test]
+    fn selection_range() {
+        let o = FindOptions {
+            range: Some((2, 8)),
+            ..opts("ab")
+        };
+        assert_eq!(ranges("ab ab ab ab", &o), vec![(3, 5), (6, 8)]);
+    }
+
+    #[test]
+    fn only_in_tokens() {
+        let o = FindOptions {
+            tokens: 3,
+            ..opts("area")
+        };
+        assert_eq!(ranges("node.area = \"area\"", &o), vec![(13, 17)]);
+    }
+}
@@ -11,14 +11,80 @@ pub struct Window {
     #[template_child]
     pub menu_functions: TemplateChild<gtk::gio::Menu>,
     #[template_child]
+    pub menu_node: TemplateChild<gtk::gio::Menu>,
+    #[template_child]
     pub txt_browse: TemplateChild<gtk::Text>,
     #[template_child]
+    pub nb_editor: TemplateChild<gtk::Notebook>,
+    #[template_child]
+    pub sb_find: TemplateChild<gtk::SearchBar>,
+    #[template_child]
+    pub txt_find: TemplateChild<gtk::SearchEntry>,
+    #[template_child]
+    pub tb_find_regex: TemplateChild<gtk::ToggleButton>,
+    #[template_child]
+    pub tb_find_case: TemplateChild<gtk::ToggleButton>,
+    #[template_child]
+    pub tb_find_selection: TemplateChild<gtk::ToggleButton>,
+    #[template_child]
+    pub dd_find_tokens: TemplateChild<gtk::DropDown>,
+    #[template_child]
+    pub btn_find_prev: TemplateChild<gtk::Button>,
+    #[template_child]
+    pub btn_find_next: TemplateChild<gtk::Button>,
+    #[template_child]
+    pub lab_find: TemplateChild<gtk::Label>,
+    #[template_child]
+    pub txt_replace: TemplateChild<gtk::Entry>,
+    #[template_child]
+    pub btn_replace: TemplateChild<gtk::Button>,
+    #[template_child]
+    pub btn_replace_all: TemplateChild<gtk::Button>,
+    #[template_child]
     pub btn_browse: TemplateChild<gtk::Button>,
     #[template_child]
     pub btn_save: TemplateChild<gtk::Button>,
     #[template_child]
+    pub sb_network: TemplateChild<gtk::SearchBar>,
+    #[template_child]
+    pub txt_search: TemplateChild<gtk::SearchEntry>,
+    #[template_child]
+    pub lab_search: TemplateChild<gtk::Label>,
+    #[template_child]
+    pub nb_network: TemplateChild<gtk::Notebook>,
+    #[template_child]
+    pub cv_table: TemplateChild<gtk::ColumnView>,
+    #[template_child]
+    pub txt_plot_series: TemplateChild<gtk::Entry>,
+    #[template_child]
+    pub txt_plot_x: TemplateChild<gtk::Entry>,
+    #[template_child]
+    pub dd_plot_kind: TemplateChild<gtk::DropDown>,
+    #[template_child]
+    pub btn_plot: TemplateChild<gtk::Button>,
+    #[template_child]
+    pub txt_plot_attr_x: TemplateChild<gtk::Entry>,
+    #[template_child]
+    pub txt_plot_attr_y: TemplateChild<gtk::Entry>,
+    #[template_child]
+    pub txt_plot_filter: TemplateChild<gtk::Entry>,
+    #[template_child]
+    pub btn_plot_scatter: TemplateChild<gtk::Button>,
+    #[template_child]
+    pub btn_plot_hist: TemplateChild<gtk::Button>,
+    #[template_child]
+    pub btn_plot_export: TemplateChild<gtk::Button>,
+    #[template_child]
+    pub da_plot: TemplateChild<gtk::DrawingArea>,
+    #[template_child]
+    pub sw_network: TemplateChild<gtk::ScrolledWindow>,
+    #[template_child]
     pub da_network: TemplateChild<gtk::DrawingArea>,
     #[template_child]
+    pub da_minimap: TemplateChild<gtk::DrawingArea>,
+    #[template_child]
+    pub btn_minimap: TemplateChild<gtk::ToggleButton>,
+    #[template_child]
     pub btn_sig: TemplateChild<gtk::Button>,
     #[template_child]
     pub lab_signature: TemplateChild<gtk::Label>,
@@ -37,6 +103,14 @@ pub struct Window {
     #[template_child]
     pub btn_export: TemplateChild<gtk::Button>,
     #[template_child]
+    pub btn_export_all: TemplateChild<gtk::Button>,
+    #[template_child]
+    pub box_figures: TemplateChild<gtk::Box>,
+    #[template_child]
+    pub txt_filter: TemplateChild<gtk::Entry>,
+    #[template_child]
+    pub lab_filter: TemplateChild<gtk::Label>,
+    #[template_child]
     pub btn_term_top: TemplateChild<gtk::Button>,
     #[template_child]
     pub btn_term_up: TemplateChild<gtk::Button>,
@@ -45,6 +119,16 @@ pub struct Window {
     #[template_child]
     pub btn_term_bottom: TemplateChild<gtk::Button>,
     #[template_child]
+    pub btn_outline: TemplateChild<gtk::ToggleButton>,
+    #[template_child]
+    pub box_outline: TemplateChild<gtk::Box>,
+    #[template_child]
+    pub lb_outline: TemplateChild<gtk::ListBox>,
+    #[template_child]
+    pub exp_problems: TemplateChild<gtk::Expander>,
+    #[template_child]
+    pub lb_problems: TemplateChild<gtk::ListBox>,
+    #[template_child]
     pub txt_term: TemplateChild<gtk::Entry>,
     #[template_child]
     pub pb_term: TemplateChild<gtk::ProgressBar>,
@@ -80,6 +164,18 @@ impl ObjectImpl for Window {
         obj.setup_callbacks();
         obj.setup_actions();
         obj.setup_drawing_area();
+        obj.setup_search();
+        obj.setup_filter();
+        obj.setup_minimap();
+        obj.setup_table();
+        obj.setup_plot();
+        obj.setup_completion();
+        obj.setup_brackets();
+        obj.setup_diagnostics();
+        obj.setup_find();
+        obj.setup_outline();
+        obj.setup_tabs();
+        obj.setup_figures();
         obj.setup_term();
         obj.setup_menu();
     }
@@ -89,7 +185,16 @@ impl ObjectImpl for Window {
 impl WidgetImpl for Window {}
 
 // Trait shared by all windows
-impl WindowImpl for Window {}
+impl WindowImpl for Window {
+    fn close_request(&self) -> glib::Propagation {
+        // ask about the unsaved tabs first
+        if self.obj().confirm_close() {
+            self.parent_close_request()
+        } else {
+            glib::Propagation::Stop
+        }
+    }
+}
 
 // Trait shared by all application windows
 impl ApplicationWindowImpl for Window {}
@@ -1,10 +1,22 @@
+mod brackets;
+mod completion;
+mod diagnostics;
+mod figures;
+mod find;
 mod imp;
+mod outline;
+mod plot;
+mod rename;
+mod table;
+mod tabs;
 use super::network;
+use crate::figures::{with_figure, DrawAttrs};
 use crate::tokens::TokenMarkup;
+use crate::view::{Highlight, Minimap, NetworkView, RenderCache};
 use gio::ActionEntry;
 use glib::{clone, Object};
 use gtk::subclass::prelude::*;
-use gtk::{gio, glib, Application, TextBuffer};
+use gtk::{gdk, gio, glib, Application, TextBuffer};
 use gtk::{prelude::*, TextIter};
 use itertools::Itertools;
 use nadi_core::parser::tokenizer::{self, TaskToken, Token};
@@ -20,6 +32,12 @@ use std::fs::File;
 use std::io::{Read, Write};
 use std::iter::Iterator;
 
+/// tags of the editor buffer used for the syntax highlighting
+const SYNTAX_TAGS: [&str; 12] = [
+    "comment", "keyword", "function", "variable", "bool", "string", "number", "datetime",
+    "pathsep", "symbols", "equal", "error2",
+];
+
 glib::wrapper! {
     pub struct Window(ObjectSubclass<imp::Window>)
         @extends gtk::ApplicationWindow, gtk::Window, gtk::Widget,
@@ -34,9 +52,14 @@ impl Window {
     }
 
     fn setup_data(&self) {
-        let ctx = TaskContext::new(None);
+        let mut ctx = TaskContext::new(None);
+        let requests = crate::functions::register(&mut ctx.functions);
         unsafe {
             self.imp().da_network.set_data("tasks_ctx", ctx);
+            self.imp().da_network.set_data("gui_requests", requests);
+            self.imp()
+                .da_network
+                .set_data("network_view", NetworkView::default());
         }
     }
 
@@ -152,7 +175,9 @@ impl Window {
             .build();
         let action_save = ActionEntry::builder("save")
             .activate(|window: &Window, _, _| {
-                let _ = window.save_file();
+                if let Err(e) = window.save_file() {
+                    window.feed_term_text(&format!("Error saving file: {e}\n"));
+                }
             })
             .build();
         let action_save_as = ActionEntry::builder("saveas")
@@ -162,6 +187,9 @@ impl Window {
             .build();
         let action_refresh = ActionEntry::builder("refresh")
             .activate(|window: &Window, _, _| {
+                if let Some(view) = window.network_view() {
+                    view.invalidate();
+                }
                 window.imp().da_network.queue_draw();
             })
             .build();
@@ -200,6 +228,36 @@ impl Window {
                 window.toggle_comment();
             })
             .build();
+        let action_run_section = ActionEntry::builder("run_section")
+            .activate(|window: &Window, _, _| {
+                window.run_section();
+            })
+            .build();
+        let action_rename = ActionEntry::builder("rename_attr")
+            .activate(|window: &Window, _, _| {
+                window.rename_attribute();
+            })
+            .build();
+        let action_highlight_up = ActionEntry::builder("highlight_upstream")
+            .activate(|window: &Window, _, _| {
+                window.highlight_node(true);
+            })
+            .build();
+        let action_highlight_down = ActionEntry::builder("highlight_downstream")
+            .activate(|window: &Window, _, _| {
+                window.highlight_node(false);
+            })
+            .build();
+        let action_clear_highlight = ActionEntry::builder("clear_highlight")
+            .activate(|window: &Window, _, _| {
+                window.clear_highlight();
+            })
+            .build();
+        let action_paste_selection = ActionEntry::builder("paste_selection")
+            .activate(|window: &Window, _, _| {
+                window.paste_selection();
+            })
+            .build();
         let action_book = ActionEntry::builder("book")
             .activate(|window: &Window, _, _| {
                 window.book();
@@ -221,9 +279,15 @@ impl Window {
             action_run_func,
             action_run_line,
             action_run_buffer,
+            action_run_section,
             action_run_term,
             action_help,
             action_comment,
+            action_rename,
+            action_highlight_up,
+            action_highlight_down,
+            action_clear_highlight,
+            action_paste_selection,
             action_book,
             action_about,
         ]);
@@ -242,7 +306,11 @@ impl Window {
         self.imp().btn_save.connect_clicked(clone!(
             #[weak(rename_to=window)]
             self,
-            move |_| window.save_file().unwrap()
+            move |_| {
+                if let Err(e) = window.save_file() {
+                    window.feed_term_text(&format!("Error saving file: {e}\n"));
+                }
+            }
         ));
 
         self.imp().btn_export.connect_clicked(clone!(
@@ -257,19 +325,6 @@ impl Window {
             move |_| window.help_line()
         ));
 
-        self.imp()
-            .tv_frame
-            .buffer()
-            .connect_cursor_position_notify(clone!(
-                #[weak(rename_to=window)]
-                self,
-                move |buf| {
-                    // let buf = window.imp().tv_frame.buffer();
-                    let mark = buf.iter_at_mark(&buf.get_insert());
-                    window.display_signature_editor(mark);
-                }
-            ));
-
         // self.imp().tv_frame.connect_insert_at_cursor(move |tv, s| {
         //     println!("Inserted {s}");
         //     if s == "0" {
@@ -277,18 +332,6 @@ impl Window {
         //     }
         // });
 
-        self.imp().tv_frame.buffer().connect_changed(clone!(
-            #[weak(rename_to=window)]
-            self,
-            move |_| {
-                let tb = window.imp().tv_frame.buffer();
-                // todo, only do this for current line
-                tb.remove_all_tags(&tb.start_iter(), &tb.end_iter());
-                window.refresh_signature();
-                window.format_task(&tb);
-            }
-        ));
-
         self.imp().btn_run_func.connect_clicked(clone!(
             #[weak(rename_to=window)]
             self,
@@ -354,6 +397,48 @@ impl Window {
         ));
     }
 
+    /// Connect the signals of an editor buffer, each tab has its own
+    fn setup_buffer(&self, buf: &TextBuffer) {
+        buf.connect_cursor_position_notify(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |buf| {
+                if in_bulk_edit(buf) {
+                    return;
+                }
+                let mark = buf.iter_at_mark(&buf.get_insert());
+                window.display_signature_editor(mark);
+            }
+        ));
+
+        // lines changed since the last highlight, and since the last
+        // check of the tasks
+        unsafe {
+            buf.set_data("dirty_lines", (i32::MAX, -1));
+            buf.set_data("check_lines", (0, i32::MAX));
+        }
+        buf.connect_insert_text(move |tb, iter, text| {
+            let lines = text.matches('\n').count() as i32;
+            mark_dirty(tb, iter.line(), iter.line() + lines);
+        });
+        buf.connect_delete_range(move |tb, start, _| mark_dirty(tb, start.line(), start.line()));
+        buf.connect_changed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |tb| {
+                if in_bulk_edit(tb) {
+                    return;
+                }
+                window.refresh_signature();
+                window.format_task(tb);
+            }
+        ));
+        self.setup_buffer_completion(buf);
+        self.setup_buffer_diagnostics(buf);
+        self.setup_buffer_find(buf);
+        self.setup_buffer_brackets(buf);
+    }
+
     fn toggle_comment(&self) {
         let buf = self.imp().tv_frame.buffer();
         let mut mark = buf.iter_at_mark(&buf.selection_bound());
@@ -416,7 +501,7 @@ impl Window {
         nadi_core::parser::tasks::parse(tokens).ok()
     }
 
-    fn task_at_mark(&self) -> (TextIter, String, Vec<Task>) {
+    fn task_at_mark(&self) -> (TextIter, TextIter, String, Vec<Task>) {
         let buf = self.imp().tv_frame.buffer();
         let mut ins = buf.iter_at_mark(&buf.get_insert());
         let mut line = ins.line();
@@ -489,14 +574,21 @@ impl Window {
                 }
             };
         }
-        (ins, text, tasks)
+        (mark, ins, text, tasks)
     }
 
     fn run_func(&self) {
         let buf = self.imp().tv_frame.buffer();
-        let (ins, tokens, tasks) = self.task_at_mark();
+        let (start, ins, tokens, tasks) = self.task_at_mark();
+        if tasks.is_empty() && !tokens.is_empty() {
+            self.feed_term_text(
+                "No complete task at the cursor, check for unclosed brackets or quotes\n",
+            );
+            return;
+        }
         self.feed_term_tasks(&tokens);
-        self.run_tasks(tasks);
+        let lines = self.task_lines(&start, &ins);
+        self.run_tasks(tasks, Some(lines));
         buf.place_cursor(&ins);
         self.imp()
             .tv_frame
@@ -516,17 +608,50 @@ impl Window {
         };
         self.feed_term_tasks(&buf.text(&mark, &ins, true));
         if let Some(t) = self.task_betwn_marks(&mark, &ins) {
-            self.run_tasks(t);
+            let lines = self.task_lines(&mark, &ins);
+            self.run_tasks(t, Some(lines));
         }
     }
 
     fn run_buffer(&self) {
-        let buf = self.imp().tv_frame.buffer();
+        self.run_text_buffer(&self.imp().tv_frame.buffer(), None);
+    }
+
+    /// Run all the tasks in the buffer of any tab, the errors of the
+    /// other tabs are reported with the name of the tab
+    fn run_text_buffer(&self, buf: &TextBuffer, name: Option<&str>) {
         let mark = buf.start_iter();
         let ins = buf.end_iter();
-        self.feed_term_tasks(&buf.text(&mark, &ins, true));
-        if let Some(t) = self.task_betwn_marks(&mark, &ins) {
-            self.run_tasks(t);
+        let text = buf.text(&mark, &ins, true);
+        self.feed_term_tasks(&text);
+        // errors are only shown in the buffer of the current tab
+        let current = *buf == self.imp().tv_frame.buffer();
+        let name = name.filter(|_| !current);
+        let tasks = match tokenizer::get_tokens(&text) {
+            Ok(tk) => nadi_core::parser::tasks::parse(tk),
+            Err(e) => {
+                if let Some(name) = name {
+                    self.feed_term_text(&format!("Error in {name}:\n"));
+                    self.feed_term_markup(&e.user_msg(None));
+                }
+                return;
+            }
+        };
+        match tasks {
+            Ok(t) => {
+                let lines = current.then(|| self.task_lines(&mark, &ins));
+                if !self.run_tasks(t, lines) {
+                    if let Some(name) = name {
+                        self.feed_term_text(&format!("Stopped running {name} at the error\n"));
+                    }
+                }
+            }
+            Err(e) => {
+                if let Some(name) = name {
+                    self.feed_term_text(&format!("Error in {name}:\n"));
+                    self.term_parse_err(e);
+                }
+            }
         }
     }
 
@@ -536,7 +661,7 @@ impl Window {
         match tokenizer::get_tokens(&text) {
             Ok(tk) => match nadi_core::parser::tasks::parse(tk) {
                 Ok(t) => {
-                    self.run_tasks(t);
+                    self.run_tasks(t, None);
                     self.imp().txt_term.set_text("");
                 }
                 Err(e) => {
@@ -549,7 +674,9 @@ impl Window {
         }
     }
 
-    fn run_tasks(&self, tasks: Vec<Task>) {
+    /// Run the tasks, `lines` are the editor lines where they start to
+    /// show the errors in the editor; returns whether all of them ran
+    fn run_tasks(&self, tasks: Vec<Task>, lines: Option<Vec<i32>>) -> bool {
         let term = &self.imp().tv_term;
         let tasks_ctx =
             if let Some(ctx) = unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") } {
@@ -558,7 +685,7 @@ impl Window {
             } else {
                 term.buffer()
                     .insert_at_cursor("No Task Context Set; shouldn't happen; contact developers");
-                return;
+                return false;
             };
         // temp solution, make NadiFunctions take a std::io::Write or
         // other trait object that can either print to stdout, or take the
@@ -567,6 +694,10 @@ impl Window {
         let mut output = String::new();
 
         let total = tasks.len();
+        let mut completed = true;
+        if lines.is_some() {
+            self.clear_runtime_errors();
+        }
         for (i, fc) in tasks.into_iter().enumerate() {
             let res = tasks_ctx.execute(fc);
             // print the stdout output to the terminal
@@ -581,10 +712,16 @@ impl Window {
                 Err(p) => {
                     self.feed_term_text(&p);
                     self.feed_term_text("\n");
+                    // lines don't match the tasks if the task spans multiple lines
+                    if let Some(l) = lines.as_ref().filter(|l| l.len() == total) {
+                        self.add_runtime_error(l[i], &p);
+                    }
+                    completed = false;
                     break;
                 }
                 _ => (),
             }
+            self.process_gui_requests();
             self.imp()
                 .pb_term
                 .set_fraction((i + 1) as f64 / total as f64);
@@ -594,7 +731,16 @@ impl Window {
         }
         self.term_prompt();
         // since the task could have changed the network properties
+        if let Some(view) = self.network_view() {
+            view.invalidate();
+        }
+        self.refresh_figures();
+        // the attributes used in the filter might have changed
+        self.apply_filter();
         self.imp().da_network.queue_draw();
+        self.imp().da_minimap.queue_draw();
+        self.refresh_table();
+        completed
     }
 
     fn refresh_signature(&self) {
@@ -741,9 +887,36 @@ impl Window {
         }
     }
 
+    /// Highlight the tasks around the lines changed since the last call
     fn format_task(&self, tb: &gtk::TextBuffer) {
-        let mut point = tb.start_iter();
-        apply_tags(&mut point, tb)
+        let (first, last) = unsafe { tb.data::<(i32, i32)>("dirty_lines") }
+            .map(|d| *unsafe { d.as_ref() })
+            .unwrap_or((0, i32::MAX));
+        unsafe {
+            tb.set_data("dirty_lines", (i32::MAX, -1));
+        }
+        if last < first {
+            return;
+        }
+        let (mut point, mut end) = task_bounds(tb, first, last);
+        // an unclosed string changes the highlight of the rest of the
+        // buffer, and so does closing it in a later pass
+        let unclosed = tokenizer::get_tokens(&tb.text(&point, &end, true))
+            .map(|t| t.iter().any(|t| t.ty == TaskToken::Quote))
+            .unwrap_or(true);
+        let was_unclosed = unsafe { tb.data::<bool>("highlight_to_end") }
+            .map(|d| *unsafe { d.as_ref() })
+            .unwrap_or_default();
+        unsafe {
+            tb.set_data("highlight_to_end", unclosed);
+        }
+        if unclosed || was_unclosed {
+            end = tb.end_iter();
+        }
+        for tag in SYNTAX_TAGS {
+            tb.remove_tag_by_name(tag, &point, &end);
+        }
+        apply_tags(&mut point, &end, tb)
     }
 
     pub fn open(&self) {
@@ -824,23 +997,58 @@ impl Window {
     }
 
     pub fn reload_network(&self) -> anyhow::Result<()> {
-        let tasks_ctx = TaskContext::new(None);
+        // the selected node is found again by name in the new network
+        let selected = match unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") } {
+            Some(c) => self
+                .network_view()
+                .and_then(|v| v.selected)
+                .and_then(|i| unsafe { c.as_ref() }.network.node(i))
+                .map(|n| n.lock().name().to_string()),
+            None => None,
+        };
+        let mut tasks_ctx = TaskContext::new(None);
+        let requests = crate::functions::register(&mut tasks_ctx.functions);
         unsafe {
             self.imp().da_network.set_data("tasks_ctx", tasks_ctx);
+            self.imp().da_network.set_data("gui_requests", requests);
+        }
+        if let Some(view) = self.network_view() {
+            view.reset_nodes();
+        }
+        for (name, buf) in self.tabs_to_run() {
+            self.run_text_buffer(&buf, Some(&name));
         }
-        self.run_buffer();
+        self.refresh_figures();
+        self.apply_filter();
+        self.search_nodes(&self.imp().txt_search.text());
+        let selected = match unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") } {
+            Some(c) => selected
+                .and_then(|n| unsafe { c.as_ref() }.network.node_by_name(&n))
+                .map(|n| n.lock().index()),
+            None => None,
+        };
+        self.select_node(selected);
+        self.select_table_row(selected);
         self.imp().da_network.queue_draw();
+        self.imp().da_minimap.queue_draw();
         Ok(())
     }
 
     pub fn save_file(&self) -> anyhow::Result<()> {
         let name = self.imp().txt_browse.text();
+        if name.is_empty() {
+            // untitled tab, ask for the file name first
+            self.save_file_as();
+            return Ok(());
+        }
         let buf = self.imp().tv_frame.buffer();
         let txt = buf
             .text(&buf.start_iter(), &buf.end_iter(), true)
             .to_string();
         let mut file = File::create(&name)?;
         file.write_all(txt.as_bytes())?;
+        buf.set_modified(false);
+        self.set_tab_path(&name);
         if self.imp().btn_sync.is_active() {
             self.reload_network()?
         }
@@ -849,11 +1057,7 @@ impl Window {
 
     pub fn new_file(&self) -> anyhow::Result<()> {
         self.browse_new_file(|w| {
-            let buf = w.imp().tv_frame.buffer();
-            w.imp()
-                .tv_frame
-                .buffer()
-                .delete(&mut buf.start_iter(), &mut buf.end_iter());
+            w.add_tab(None, &w.imp().txt_browse.text());
         });
         Ok(())
     }
@@ -898,48 +1102,63 @@ impl Window {
     pub fn open_file(&self, file: &gtk::gio::File) -> anyhow::Result<()> {
         let filename = file.path().expect("Couldn't get file path");
         let name = filename.to_string_lossy().to_string();
-        self.imp().txt_browse.set_text(&name);
-        let txt = std::fs::read_to_string(&name)?;
-        self.imp().tv_frame.buffer().set_text(&txt);
+        if self.modified_tab(&name).is_some() {
+            // show the tab with the changes, and ask before reloading it
+            self.open_tab(&name);
+            let detail =
+                format!("{name} is open with unsaved changes, reloading it discards them.");
+            self.ask_unsaved(&detail, move |window, choice| {
+                let res = match choice {
+                    tabs::Unsaved::Save => window.save_file(),
+                    tabs::Unsaved::Discard => window.load_file(&name),
+                    tabs::Unsaved::Cancel => Ok(()),
+                };
+                if let Err(e) = res {
+                    window.feed_term_text(&format!("{e}\n"));
+                }
+            });
+            return Ok(());
+        }
+        self.load_file(&name)
+    }
+
+    fn load_file(&self, name: &str) -> anyhow::Result<()> {
+        let txt = std::fs::read_to_string(name)?;
+        let buf = self.open_tab(name);
+        buf.set_text(&txt);
+        buf.set_modified(false);
         self.refresh_signature();
         self.reload_network()
     }
 
     pub fn export_file(&self, file: &gtk::gio::File) {
-        let filename = file.path().expect("Couldn't get file path");
+        let Some(filename) = file.path() else {
+            self.feed_term_text("Couldn't get the path of the file to export\n");
+            return;
+        };
         let name = filename.to_string_lossy().to_string();
-        if let Some(tctx) = unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") } {
-            let tctx: &mut TaskContext = unsafe { &mut *tctx.as_ptr() };
-            let net: &Network = &tctx.network;
-            let mut svg = cairo::SvgSurface::new::<&str>(400.0, 500.0, None).unwrap();
-            let ctx = cairo::Context::new(&mut svg).unwrap();
-            let (mut h, mut w) = network::calc_hw(net, &ctx);
-            h += 50;
-            w += 50;
-            let ext: &str = name.as_str().split('.').last().unwrap();
-            match ext {
-                "svg" => {
-                    let mut svg = cairo::SvgSurface::new(w as f64, h as f64, Some(name)).unwrap();
-                    let ctx = cairo::Context::new(&mut svg).unwrap();
-                    network::draw_network(net, &ctx, w, h, None);
-                }
-                "pdf" => {
-                    let mut pdf = cairo::PdfSurface::new(w as f64, h as f64, name).unwrap();
-                    let ctx = cairo::Context::new(&mut pdf).unwrap();
-                    network::draw_network(net, &ctx, w, h, None);
-                }
-                "png" => {
-                    let mut png =
-                        cairo::ImageSurface::create(cairo::Format::ARgb32, w * 10, h * 10).unwrap();
-                    let ctx = cairo::Context::new(&mut png).unwrap();
-                    ctx.scale(10.0, 10.0);
-                    network::draw_network(net, &ctx, w, h, None);
-                    let mut f = File::create(name).unwrap();
-                    png.write_to_png(&mut f).unwrap();
-                }
-                _ => (),
-            }
+        let view = self
+            .network_view()
+            .map(|v| v.for_export())
+            .unwrap_or_default();
+        self.export_view(name, view);
+    }
+
+    /// Export the network drawn with the given view to the file, errors
+    /// are shown in the terminal
+    fn export_view(&self, name: String, mut view: NetworkView) -> bool {
+        let Some(tctx) = (unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") }) else {
+            return false;
+        };
+        let net = &unsafe { tctx.as_ref() }.network;
+        let figure = view.figure.clone();
+        let res = with_figure(net, figure.as_deref(), |attrs| {
+            export_network(net, attrs, name, &mut view)
+        });
+        if let Err(e) = &res {
+            self.feed_term_text(&format!("{e}\n"));
         }
+        res.is_ok()
     }
 
     fn setup_drawing_area(&self) {
@@ -947,11 +1166,486 @@ impl Window {
         self.imp().da_network.set_draw_func(move |da, ctx, w, h| {
             // network data will be available when a new network is loaded.
             // TODO, make a different network data type for graph/plots
-            if let Some(tctx) = unsafe { da.data::<TaskContext>("tasks_ctx") } {
-                let tctx: &TaskContext = unsafe { &*tctx.as_ptr() };
-                network::draw_network(&tctx.network, ctx, w, h, Some(da));
+            if let (Some(tctx), Some(view)) = unsafe {
+                (
+                    da.data::<TaskContext>("tasks_ctx"),
+                    da.data::<NetworkView>("network_view"),
+                )
+            } {
+                let net = &unsafe { tctx.as_ref() }.network;
+                let view: &mut NetworkView = unsafe { &mut *view.as_ptr() };
+                let figure = view.figure.clone();
+                let key = (view.revision, w, h);
+                let cached = match unsafe { da.data::<RenderCache>("render_cache") } {
+                    Some(c) => unsafe { c.as_ref() }.key == key,
+                    None => false,
+                };
+                if !cached {
+                    let Ok(surface) =
+                        cairo::RecordingSurface::create(cairo::Content::ColorAlpha, None)
+                    else {
+                        with_figure(net, figure.as_deref(), |attrs| {
+                            network::draw_network(net, attrs, ctx, w, h, Some(da), view)
+                        });
+                        let _ = network::draw_overlay(ctx, view);
+                        return;
+                    };
+                    if let Ok(rctx) = cairo::Context::new(&surface) {
+                        with_figure(net, figure.as_deref(), |attrs| {
+                            network::draw_network(net, attrs, &rctx, w, h, Some(da), view)
+                        });
+                    }
+                    unsafe { da.set_data("render_cache", RenderCache { surface, key }) };
+                }
+                if let Some(cache) = unsafe { da.data::<RenderCache>("render_cache") } {
+                    let cache: &RenderCache = unsafe { cache.as_ref() };
+                    // grouped so the fading of the overlay doesn't reach
+                    // the background of the widget
+                    ctx.push_group();
+                    if ctx.set_source_surface(&cache.surface, 0.0, 0.0).is_ok() {
+                        let _ = ctx.paint();
+                    }
+                    let _ = network::draw_overlay(ctx, view);
+                    if ctx.pop_group_to_source().is_ok() {
+                        let _ = ctx.paint();
+                    }
+                }
             }
         });
+        let click = gtk::GestureClick::new();
+        click.connect_pressed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |g, _, x, y| {
+                if let Some(w) = g.widget() {
+                    w.grab_focus();
+                }
+                if !window.toggle_collapse_at(x, y) {
+                    let node = window.network_view().and_then(|v| v.node_at(x, y));
+                    window.select_node(node);
+                    window.select_table_row(node);
+                }
+            }
+        ));
+        self.imp().da_network.add_controller(click);
+
+        let popover = gtk::PopoverMenu::from_model(Some(&*self.imp().menu_node));
+        popover.set_parent(&*self.imp().da_network);
+        popover.set_has_arrow(false);
+        let context = gtk::GestureClick::new();
+        context.set_button(gdk::BUTTON_SECONDARY);
+        context.connect_pressed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_, _, x, y| {
+                let Some(view) = window.network_view() else {
+                    return;
+                };
+                view.context_node = view.node_at(x, y);
+                if view.context_node.is_some() || view.highlight.is_some() {
+                    popover.set_pointing_to(Some(&gdk::Rectangle::new(x as i32, y as i32, 1, 1)));
+                    popover.popup();
+                }
+            }
+        ));
+        self.imp().da_network.add_controller(context);
+    }
+
+    fn setup_minimap(&self) {
+        let imp = self.imp();
+        imp.btn_minimap
+            .bind_property("active", &*imp.da_minimap, "visible")
+            .sync_create()
+            .build();
+        imp.da_minimap.set_draw_func(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_, ctx, w, h| {
+                let _ = window.draw_minimap(ctx, w, h);
+            }
+        ));
+        let sw = &imp.sw_network;
+        for adj in [sw.hadjustment(), sw.vadjustment()] {
+            adj.connect_value_changed(clone!(
+                #[weak(rename_to=window)]
+                self,
+                move |_| window.imp().da_minimap.queue_draw()
+            ));
+            adj.connect_changed(clone!(
+                #[weak(rename_to=window)]
+                self,
+                move |_| window.imp().da_minimap.queue_draw()
+            ));
+        }
+        let drag = gtk::GestureDrag::new();
+        drag.connect_drag_begin(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_, x, y| window.minimap_navigate(x, y)
+        ));
+        drag.connect_drag_update(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |g, dx, dy| {
+                if let Some((x, y)) = g.start_point() {
+                    window.minimap_navigate(x + dx, y + dy);
+                }
+            }
+        ));
+        imp.da_minimap.add_controller(drag);
+    }
+
+    /// Draw the cached network thumbnail with the current viewport
+    fn draw_minimap(&self, ctx: &cairo::Context, w: i32, h: i32) -> anyhow::Result<()> {
+        let tctx = match unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") } {
+            Some(c) => unsafe { c.as_ref() },
+            None => return Ok(()),
+        };
+        let Some(view) = self.network_view() else {
+            return Ok(());
+        };
+        let da = &self.imp().da_network;
+        let (fw, fh) = (da.width(), da.height());
+        if fw == 0 || fh == 0 || tctx.network.nodes_count() == 0 {
+            return Ok(());
+        }
+        let key = (view.revision, fw, fh);
+        let outdated = match unsafe { self.imp().da_minimap.data::<Minimap>("minimap") } {
+            Some(m) => unsafe { m.as_ref() }.key != key,
+            None => true,
+        };
+        if outdated {
+            let scale = (w as f64 / fw as f64).min(h as f64 / fh as f64);
+            let surface = cairo::ImageSurface::create(
+                cairo::Format::ARgb32,
+                (fw as f64 * scale).ceil() as i32,
+                (fh as f64 * scale).ceil() as i32,
+            )?;
+            let sctx = cairo::Context::new(&surface)?;
+            sctx.set_source_rgb(1.0, 1.0, 1.0);
+            sctx.paint()?;
+            sctx.scale(scale, scale);
+            let mut mview = view.for_export();
+            let net = &tctx.network;
+            with_figure(net, view.figure.as_deref(), |attrs| {
+                network::draw_network(net, attrs, &sctx, fw, fh, None, &mut mview)
+            });
+            drop(sctx);
+            unsafe {
+                self.imp().da_minimap.set_data(
+                    "minimap",
+                    Minimap {
+                        surface,
+                        key,
+                        scale,
+                    },
+                );
+            }
+        }
+        let Some(minimap) = (unsafe { self.imp().da_minimap.data::<Minimap>("minimap") }) else {
+            return Ok(());
+        };
+        let minimap: &Minimap = unsafe { minimap.as_ref() };
+        let scale = minimap.scale;
+        ctx.set_source_surface(&minimap.surface, 0.0, 0.0)?;
+        ctx.paint()?;
+        ctx.set_line_width(1.0);
+        ctx.set_source_rgb(0.5, 0.5, 0.5);
+        ctx.rectangle(
+            0.5,
+            0.5,
+            minimap.surface.width() as f64 - 1.0,
+            minimap.surface.height() as f64 - 1.0,
+        );
+        ctx.stroke()?;
+        let sw = &self.imp().sw_network;
+        let (hadj, vadj) = (sw.hadjustment(), sw.vadjustment());
+        ctx.rectangle(
+            hadj.value() * scale,
+            vadj.value() * scale,
+            hadj.page_size() * scale,
+            vadj.page_size() * scale,
+        );
+        ctx.set_source_rgba(1.0, 0.0, 0.0, 0.15);
+        ctx.fill_preserve()?;
+        ctx.set_source_rgb(1.0, 0.0, 0.0);
+        ctx.stroke()?;
+        Ok(())
+    }
+
+    /// Center the network view on the point in the minimap
+    fn minimap_navigate(&self, x: f64, y: f64) {
+        let Some(minimap) = (unsafe { self.imp().da_minimap.data::<Minimap>("minimap") }) else {
+            return;
+        };
+        let scale = unsafe { minimap.as_ref() }.scale;
+        let sw = &self.imp().sw_network;
+        let (hadj, vadj) = (sw.hadjustment(), sw.vadjustment());
+        hadj.set_value(x / scale - hadj.page_size() / 2.0);
+        vadj.set_value(y / scale - vadj.page_size() / 2.0);
+    }
+
+    /// Toggle the collapse of the subtree if there is a handle at the
+    /// point, returns whether there was one
+    fn toggle_collapse_at(&self, x: f64, y: f64) -> bool {
+        let tctx = match unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") } {
+            Some(c) => unsafe { &*c.as_ptr() },
+            None => return false,
+        };
+        let Some(view) = self.network_view() else {
+            return false;
+        };
+        let Some(ind) = view.handle_at(x, y) else {
+            return false;
+        };
+        if let Some(n) = tctx.network.node(ind) {
+            let name = n.lock().name().to_string();
+            view.toggle_collapse(&name);
+            self.imp().da_network.queue_draw();
+            self.imp().da_minimap.queue_draw();
+        }
+        true
+    }
+
+    fn select_node(&self, ind: Option<usize>) {
+        let Some(view) = self.network_view() else {
+            return;
+        };
+        if view.selected != ind {
+            view.selected = ind;
+            self.imp().da_network.queue_draw();
+        }
+    }
+
+    fn highlight_node(&self, upstream: bool) {
+        let tctx = match unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") } {
+            Some(c) => unsafe { &*c.as_ptr() },
+            None => return,
+        };
+        let Some(view) = self.network_view() else {
+            return;
+        };
+        if let Some(ind) = view.context_node {
+            view.highlight = if upstream {
+                Highlight::upstream(&tctx.network, ind)
+            } else {
+                Highlight::downstream(&tctx.network, ind)
+            };
+            self.imp().da_network.queue_draw();
+        }
+    }
+
+    fn clear_highlight(&self) {
+        if let Some(view) = self.network_view() {
+            view.highlight = None;
+            self.imp().da_network.queue_draw();
+        }
+    }
+
+    fn paste_selection(&self) {
+        let Some(view) = self.network_view() else {
+            return;
+        };
+        if let Some(h) = &view.highlight {
+            let tv = &self.imp().tv_frame;
+            tv.buffer().insert_at_cursor(&h.selection);
+            tv.grab_focus();
+        }
+    }
+
+    fn setup_search(&self) {
+        let imp = self.imp();
+        imp.sb_network.connect_entry(&*imp.txt_search);
+        let keys = gtk::EventControllerKey::new();
+        keys.connect_key_pressed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |_, key, _, state| {
+                if key == gdk::Key::f && state.contains(gdk::ModifierType::CONTROL_MASK) {
+                    window.imp().sb_network.set_search_mode(true);
+                    window.imp().txt_search.grab_focus();
+                    return glib::Propagation::Stop;
+                }
+                glib::Propagation::Proceed
+            }
+        ));
+        imp.da_network.add_controller(keys);
+
+        imp.txt_search.connect_search_changed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |e| window.search_nodes(&e.text())
+        ));
+        imp.txt_search.connect_activate(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.search_next(true)
+        ));
+        imp.txt_search.connect_next_match(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.search_next(true)
+        ));
+        imp.txt_search.connect_previous_match(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.search_next(false)
+        ));
+        imp.txt_search.connect_stop_search(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| {
+                window.search_nodes("");
+                window.imp().da_network.grab_focus();
+            }
+        ));
+        // Shift+Enter goes to the previous match
+        let keys = gtk::EventControllerKey::new();
+        keys.connect_key_pressed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |_, key, _, state| {
+                if matches!(key, gdk::Key::Return | gdk::Key::KP_Enter)
+                    && state.contains(gdk::ModifierType::SHIFT_MASK)
+                {
+                    window.search_next(false);
+                    return glib::Propagation::Stop;
+                }
+                glib::Propagation::Proceed
+            }
+        ));
+        imp.txt_search.add_controller(keys);
+    }
+
+    fn network_view(&self) -> Option<&mut NetworkView> {
+        let view = unsafe { self.imp().da_network.data::<NetworkView>("network_view") }?;
+        Some(unsafe { &mut *view.as_ptr() })
+    }
+
+    /// Indices of the nodes satisfying the propositional expression,
+    /// errors are shown in the terminal
+    fn filter_nodes(&self, filter: &str) -> Option<Vec<usize>> {
+        let tctx = unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") }?;
+        let tctx: &TaskContext = unsafe { tctx.as_ref() };
+        match crate::filter::filter_nodes(&tctx.network, filter) {
+            Ok(nodes) => Some(nodes),
+            Err(e) => {
+                self.feed_term_text(&format!("Invalid filter {filter:?}: {e}\n"));
+                None
+            }
+        }
+    }
+
+    fn setup_filter(&self) {
+        let entry = &self.imp().txt_filter;
+        entry.connect_activate(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.apply_filter()
+        ));
+        entry.connect_icon_release(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |e, _| {
+                e.set_text("");
+                window.apply_filter();
+            }
+        ));
+    }
+
+    /// Hide the nodes not satisfying the filter in the toolbar, the
+    /// network itself is not changed
+    fn apply_filter(&self) {
+        let text = self.imp().txt_filter.text();
+        let Some(tctx) = (unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") }) else {
+            return;
+        };
+        let net = &unsafe { tctx.as_ref() }.network;
+        let filter = if text.trim().is_empty() {
+            None
+        } else {
+            match crate::filter::filter_nodes(net, &text) {
+                Ok(nodes) => Some(nodes.into_iter().collect()),
+                Err(e) => {
+                    // keep the last filter if this one has errors
+                    self.feed_term_text(&format!("Invalid filter {text:?}: {e}\n"));
+                    return;
+                }
+            }
+        };
+        let total = net.nodes_count();
+        let Some(view) = self.network_view() else {
+            return;
+        };
+        let label = match &filter {
+            Some(f) => format!("{} of {total}", f.len()),
+            None => String::new(),
+        };
+        self.imp().lab_filter.set_text(&label);
+        if view.user_filter != filter {
+            view.user_filter = filter;
+            view.invalidate();
+            self.imp().da_network.queue_draw();
+            self.imp().da_minimap.queue_draw();
+            self.refresh_table();
+        }
+    }
+
+    fn search_nodes(&self, pattern: &str) {
+        let tctx = match unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") } {
+            Some(c) => unsafe { &*c.as_ptr() },
+            None => return,
+        };
+        let Some(view) = self.network_view() else {
+            return;
+        };
+        view.search(&tctx.network, pattern);
+        self.update_search_label();
+        self.imp().da_network.queue_draw();
+        if let Some(ind) = view.current_match() {
+            self.scroll_to_node(ind);
+        }
+    }
+
+    fn search_next(&self, forward: bool) {
+        let Some(view) = self.network_view() else {
+            return;
+        };
+        if let Some(ind) = view.next_match(forward) {
+            self.update_search_label();
+            self.imp().da_network.queue_draw();
+            self.scroll_to_node(ind);
+        }
+    }
+
+    fn update_search_label(&self) {
+        let Some(view) = self.network_view() else {
+            return;
+        };
+        let label = match view.current {
+            Some(c) => format!("{} of {}", c + 1, view.matches.len()),
+            None if self.imp().txt_search.text().is_empty() => String::new(),
+            None => "No Match".to_string(),
+        };
+        self.imp().lab_search.set_text(&label);
+    }
+
+    /// Center the scrolled window on the node from the last drawing
+    fn scroll_to_node(&self, ind: usize) {
+        let Some(view) = self.network_view() else {
+            return;
+        };
+        if let Some(&(x, y)) = view.positions.get(&ind) {
+            let sw = &self.imp().sw_network;
+            let vadj = sw.vadjustment();
+            vadj.set_value(y - vadj.page_size() / 2.0);
+            let hadj = sw.hadjustment();
+            hadj.set_value(x - hadj.page_size() / 2.0);
+        }
     }
 
     fn setup_term(&self) {
@@ -1110,8 +1804,117 @@ impl Window {
 //     &strs[0][0..pre]
 // }
 
-fn apply_tags(point: &mut TextIter, tb: &TextBuffer) {
-    let text = tb.text(&point, &tb.end_iter(), true);
+/// Draw the network to a svg, pdf or png file based on the extension
+fn export_network(
+    net: &Network,
+    attrs: &DrawAttrs,
+    name: String,
+    view: &mut NetworkView,
+) -> anyhow::Result<()> {
+    let ext: &str = name.split('.').last().unwrap_or_default();
+    if !matches!(ext, "svg" | "pdf" | "png") {
+        anyhow::bail!("Unsupported export format {ext:?}, use svg, pdf or png");
+    }
+    let mut svg = cairo::SvgSurface::new::<&str>(400.0, 500.0, None)?;
+    let ctx = cairo::Context::new(&mut svg)?;
+    let (mut h, mut w) = network::calc_hw(net, attrs, &ctx, view);
+    h += 50;
+    w += 50;
+    match ext {
+        "svg" => {
+            let mut svg = cairo::SvgSurface::new(w as f64, h as f64, Some(&name))?;
+            let ctx = cairo::Context::new(&mut svg)?;
+            network::draw_network(net, attrs, &ctx, w, h, None, view);
+        }
+        "pdf" => {
+            let mut pdf = cairo::PdfSurface::new(w as f64, h as f64, &name)?;
+            let ctx = cairo::Context::new(&mut pdf)?;
+            network::draw_network(net, attrs, &ctx, w, h, None, view);
+        }
+        _ => {
+            let mut png = cairo::ImageSurface::create(cairo::Format::ARgb32, w * 10, h * 10)?;
+            let ctx = cairo::Context::new(&mut png)?;
+            ctx.scale(10.0, 10.0);
+            network::draw_network(net, attrs, &ctx, w, h, None, view);
+            drop(ctx);
+            let mut f = File::create(&name)?;
+            png.write_to_png(&mut f)?;
+        }
+    }
+    Ok(())
+}
+
+fn mark_dirty(tb: &TextBuffer, first: i32, last: i32) {
+    // the highlight and the checks each take their own range
+    for key in ["dirty_lines", "check_lines"] {
+        let (f, l) = unsafe { tb.data::<(i32, i32)>(key) }
+            .map(|d| *unsafe { d.as_ref() })
+            .unwrap_or((i32::MAX, -1));
+        unsafe {
+            tb.set_data(key, (f.min(first), l.max(last)));
+        }
+    }
+}
+
+/// Make many edits to the buffer as one user action, the handlers of
+/// the `changed` signal run once after all of them
+fn bulk_edit(tb: &TextBuffer, edit: impl FnOnce()) {
+    unsafe { tb.set_data("bulk_edit", true) };
+    tb.begin_user_action();
+    edit();
+    tb.end_user_action();
+    unsafe { tb.steal_data::<bool>("bulk_edit") };
+    tb.emit_by_name::<()>("changed", &[]);
+}
+
+/// Whether the change is a part of a bulk edit
+fn in_bulk_edit(tb: &TextBuffer) -> bool {
+    unsafe { tb.data::<bool>("bulk_edit") }.is_some()
+}
+
+/// Start of the task at the first line, and of the task after the last
+/// line (or the end of the buffer)
+fn task_bounds(tb: &TextBuffer, first: i32, last: i32) -> (TextIter, TextIter) {
+    let mut line = first.min(tb.line_count() - 1);
+    while line > 0 && !is_task_start(tb, line) {
+        line -= 1;
+    }
+    let start = tb.iter_at_line(line).unwrap_or(tb.start_iter());
+    let mut line = last.max(line).saturating_add(1);
+    while line < tb.line_count() && !is_task_start(tb, line) {
+        line += 1;
+    }
+    (start, tb.iter_at_line(line).unwrap_or(tb.end_iter()))
+}
+
+/// Whether a task starts at the line
+fn is_task_start(tb: &TextBuffer, line: i32) -> bool {
+    let Some(start) = tb.iter_at_line(line) else {
+        return false;
+    };
+    let mut end = start;
+    if !end.ends_line() {
+        end.forward_to_line_end();
+    }
+    let Ok(tokens) = tokenizer::get_tokens(&tb.text(&start, &end, true)) else {
+        return false;
+    };
+    let tokens = tokenizer::VecTokens::new(tokens);
+    match tokens.peek_next_no_ws(true) {
+        Some(t) => match t.ty {
+            TaskToken::Keyword(TaskKeyword::In) | TaskToken::Keyword(TaskKeyword::Match) => false,
+            TaskToken::Keyword(_) => true,
+            _ => false,
+        },
+        None => false,
+    }
+}
+
+fn apply_tags(point: &mut TextIter, end: &TextIter, tb: &TextBuffer) {
+    if *point >= *end {
+        return;
+    }
+    let text = tb.text(&point, end, true);
     match tokenizer::get_tokens(&text) {
         Ok(tags) => apply_token_tags(point, tb, &tags),
         Err(e) => {
@@ -1127,10 +1930,9 @@ fn apply_tags(point: &mut TextIter, tb: &TextBuffer) {
                     return;
                 }
             }
-            let l = *point;
+            // the error itself is underlined by the diagnostics
             point.forward_line();
-            tb.apply_tag_by_name("error", &l, &point);
-            apply_tags(point, tb);
+            apply_tags(point, end, tb);
         }
     }
 }
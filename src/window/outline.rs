@@ -0,0 +1,333 @@
+use super::Window;
+use glib::clone;
+use gtk::glib;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use nadi_core::parser::tokenizer::{self, TaskToken, Token};
+use nadi_core::tasks::TaskKeyword;
+
+/// Longest selection shown for a task in the outline
+const MAX_SELECTION: usize = 40;
+
+/// Entry of the outline, the lines are the rows of the editor
+#[derive(Debug, Clone)]
+struct OutlineItem {
+    line: i32,
+    /// tasks till the next section, only for sections
+    section_end: Option<i32>,
+}
+
+impl Window {
+    pub(super) fn setup_outline(&self) {
+        let imp = self.imp();
+        unsafe {
+            imp.lb_outline
+                .set_data("outline", Vec::<OutlineItem>::new());
+        }
+        imp.btn_outline
+            .bind_property("active", &*imp.box_outline, "visible")
+            .sync_create()
+            .build();
+        // the outline is only kept up to date while it is shown
+        imp.btn_outline.connect_active_notify(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.refresh_outline()
+        ));
+        imp.lb_outline.connect_row_activated(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_, row| window.goto_outline(row.index())
+        ));
+    }
+
+    fn outline(&self) -> Option<&mut Vec<OutlineItem>> {
+        unsafe {
+            self.imp()
+                .lb_outline
+                .data::<Vec<OutlineItem>>("outline")
+                .map(|o| &mut *o.as_ptr())
+        }
+    }
+
+    /// Rebuild the outline if it is shown
+    pub(super) fn refresh_outline(&self) {
+        if self.imp().box_outline.is_visible() {
+            self.build_outline();
+        }
+    }
+
+    /// Rebuild the outline from the tasks and the `## Section` comments
+    /// of the current buffer
+    fn build_outline(&self) {
+        let imp = self.imp();
+        let buf = imp.tv_frame.buffer();
+        let list = &imp.lb_outline;
+        while let Some(row) = list.first_child() {
+            list.remove(&row);
+        }
+        let text = buf.text(&buf.start_iter(), &buf.end_iter(), true);
+        // nothing to show until the tokens are valid
+        let tokens = tokenizer::get_tokens(&text).unwrap_or_default();
+        let starts = outline_starts(&tokens, &text);
+        let mut items = Vec::with_capacity(starts.len());
+        let mut in_section = false;
+        for (i, &(line, first, ref title)) in starts.iter().enumerate() {
+            let next = starts.get(i + 1).map(|s| s.1).unwrap_or(tokens.len());
+            let row = match title {
+                Some(title) => {
+                    in_section = true;
+                    let end = starts[i + 1..]
+                        .iter()
+                        .find(|s| s.2.is_some())
+                        .map(|s| s.0)
+                        .unwrap_or(buf.line_count());
+                    items.push(OutlineItem {
+                        line,
+                        section_end: Some(end),
+                    });
+                    self.section_row(title, line, end)
+                }
+                None => {
+                    items.push(OutlineItem {
+                        line,
+                        section_end: None,
+                    });
+                    let label = gtk::Label::new(None);
+                    label.set_xalign(0.0);
+                    label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+                    label.set_markup(&task_summary(&tokens[first..next]));
+                    if in_section {
+                        label.set_margin_start(15);
+                    }
+                    label.upcast()
+                }
+            };
+            list.append(&row);
+        }
+        if let Some(outline) = self.outline() {
+            *outline = items;
+        }
+    }
+
+    fn section_row(&self, title: &str, start: i32, end: i32) -> gtk::Widget {
+        let label = gtk::Label::new(None);
+        label.set_markup(&format!("<b>{}</b>", glib::markup_escape_text(title)));
+        label.set_xalign(0.0);
+        label.set_hexpand(true);
+        label.set_ellipsize(gtk::pango::EllipsizeMode::End);
+        let run = gtk::Button::from_icon_name("media-playback-start-symbolic");
+        run.set_has_frame(false);
+        run.set_tooltip_text(Some("Run this Section"));
+        run.connect_clicked(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.run_lines(start, end)
+        ));
+        let row = gtk::Box::new(gtk::Orientation::Horizontal, 5);
+        row.append(&label);
+        row.append(&run);
+        row.upcast()
+    }
+
+    fn goto_outline(&self, ind: i32) {
+        let Some(item) = self.outline().and_then(|o| o.get(ind as usize)) else {
+            return;
+        };
+        let tv = &self.imp().tv_frame;
+        let buf = tv.buffer();
+        let Some(start) = buf.iter_at_line(item.line) else {
+            return;
+        };
+        buf.place_cursor(&start);
+        tv.scroll_to_mark(&buf.get_insert(), 0.1, true, 0.0, 0.0);
+        tv.grab_focus();
+    }
+
+    /// Run the section the cursor is in
+    pub(super) fn run_section(&self) {
+        if !self.imp().box_outline.is_visible() {
+            self.build_outline();
+        }
+        let buf = self.imp().tv_frame.buffer();
+        let line = buf.iter_at_mark(&buf.get_insert()).line();
+        let section = self.outline().and_then(|o| {
+            o.iter()
+                .filter_map(|i| i.section_end.map(|e| (i.line, e)))
+                .find(|(s, e)| *s <= line && line < *e)
+        });
+        match section {
+            Some((start, end)) => self.run_lines(start, end),
+            None => self.feed_term_text("The cursor is not inside a section\n"),
+        }
+    }
+
+    /// Run the tasks from the start line till the end line
+    fn run_lines(&self, start: i32, end: i32) {
+        let buf = self.imp().tv_frame.buffer();
+        let Some(mark) = buf.iter_at_line(start) else {
+            return;
+        };
+        let ins = buf.iter_at_line(end).unwrap_or(buf.end_iter());
+        self.feed_term_tasks(&buf.text(&mark, &ins, true));
+        if let Some(t) = self.task_betwn_marks(&mark, &ins) {
+            let lines = self.task_lines(&mark, &ins);
+            self.run_tasks(t, Some(lines));
+        }
+    }
+}
+
+/// Lines, indices of the first token and the section titles of the
+/// tasks and the `## Section` comments
+fn outline_starts(tokens: &[Token], text: &str) -> Vec<(i32, usize, Option<String>)> {
+    let lines: Vec<&str> = text.split('\n').collect();
+    let mut starts = vec![];
+    let mut line = 0;
+    let mut line_start = true;
+    for (i, t) in tokens.iter().enumerate() {
+        match t.ty {
+            TaskToken::WhiteSpace => (),
+            TaskToken::Keyword(TaskKeyword::In | TaskKeyword::Match) => line_start = false,
+            TaskToken::Keyword(_) if line_start => {
+                starts.push((line as i32, i, None));
+                line_start = false;
+            }
+            TaskToken::Comment if line_start => {
+                let title = lines.get(line).copied().and_then(section_title);
+                if title.is_some() {
+                    starts.push((line as i32, i, title));
+                }
+                line_start = false;
+            }
+            _ => line_start = false,
+        }
+        // strings and comments can span multiple lines
+        let newlines = t.content.matches('\n').count();
+        if newlines > 0 {
+            line += newlines;
+            line_start = t.content.ends_with('\n');
+        }
+    }
+    starts
+}
+
+/// Title of the section if the line is a `## Section` comment
+fn section_title(line: &str) -> Option<String> {
+    let title = line.trim().strip_prefix("##")?;
+    Some(title.trim_start_matches('#').trim().to_string())
+}
+
+/// Keyword, selection, attribute and function of the task as markup,
+/// or its first line in red if it is not valid
+fn task_summary(tokens: &[Token]) -> String {
+    if nadi_core::parser::tasks::parse(tokens.to_vec()).is_ok() {
+        return summarize(tokens);
+    }
+    let first: String = tokens
+        .iter()
+        .map(|t| t.content)
+        .take_while(|c| !c.contains('\n'))
+        .collect();
+    format!(
+        "<span foreground=\"red\">{}</span>",
+        glib::markup_escape_text(first.trim())
+    )
+}
+
+fn summarize(tokens: &[Token]) -> String {
+    let mut tokens = tokens
+        .iter()
+        .filter(|t| !matches!(t.ty, TaskToken::Comment | TaskToken::NewLine))
+        .skip_while(|t| matches!(t.ty, TaskToken::WhiteSpace))
+        .peekable();
+    let keyword = tokens.next().map(|t| t.content).unwrap_or_default();
+    // the node or network selection right after the keyword
+    let mut selection = String::new();
+    let mut depth = 0;
+    while let Some(t) = tokens.peek() {
+        match t.content {
+            "(" | "[" | "{" => depth += 1,
+            ")" | "]" | "}" => depth -= 1,
+            _ if depth == 0 => break,
+            _ => (),
+        }
+        selection.push_str(t.content);
+        tokens.next();
+    }
+    let selection = selection.split_whitespace().collect::<Vec<_>>().join(" ");
+    let selection = if selection.chars().count() > MAX_SELECTION {
+        let s: String = selection.chars().take(MAX_SELECTION).collect();
+        format!("{s}…")
+    } else {
+        selection
+    };
+    let mut attr = None;
+    let mut func = None;
+    let mut prev = None;
+    for t in tokens.filter(|t| !matches!(t.ty, TaskToken::WhiteSpace)) {
+        match t.ty {
+            TaskToken::Variable if attr.is_none() && prev == Some(".") => {
+                attr = Some(t.content);
+            }
+            TaskToken::Function if func.is_none() => func = Some(t.content),
+            _ => (),
+        }
+        prev = Some(t.content);
+    }
+    let mut summary = format!(
+        "<b>{}</b>{}",
+        glib::markup_escape_text(keyword),
+        glib::markup_escape_text(&selection)
+    );
+    if let Some(a) = attr {
+        summary.push_str(&format!(".{}", glib::markup_escape_text(a)));
+    }
+    if let Some(f) = func {
+        let sep = if attr.is_some() { " = " } else { " " };
+        summary.push_str(&format!("{sep}<i>{}</i>()", glib::markup_escape_text(f)));
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn starts(text: &str) -> Vec<(i32, Option<String>)> {
+        let tokens = tokenizer::get_tokens(text).unwrap();
+        outline_starts(&tokens, text)
+            .into_iter()
+            .map(|(l, _, t)| (l, t))
+            .collect()
+    }
+
+    #[test]
+    fn tasks_and_sections() {
+        let text = "## Inputs\nnode.x = 1\n# comment\nnetwork count()\n### Plots #\nnode show()\n";
+        assert_eq!(
+            starts(text),
+            vec![
+                (0, Some("Inputs".to_string())),
+                (1, None),
+                (3, None),
+                (4, Some("Plots #".to_string())),
+                (5, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn multiline_tasks() {
+        let text = "node.x = \"a\nnode b\"\nnode.y = [\n\t1,\n]\nnode.z = 2\n";
+        assert_eq!(starts(text), vec![(0, None), (2, None), (5, None)]);
+    }
+
+    #[test]
+    fn summary() {
+        let tokens = tokenizer::get_tokens("node[a -> b].area = sum(x)\n").unwrap();
+        assert_eq!(
+            summarize(&tokens),
+            "<b>node</b>[a -&gt; b].area = <i>sum</i>()"
+        );
+    }
+}
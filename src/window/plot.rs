@@ -0,0 +1,239 @@
+use super::Window;
+use crate::functions::{GuiRequest, GuiRequests};
+use crate::plot::{draw_plot, export_plot, node_at, Plot, PlotKind};
+use glib::clone;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gio, glib};
+use nadi_core::tasks::TaskContext;
+
+/// Page of the plot in the network notebook
+const PLOT_PAGE: u32 = 2;
+/// Size of the exported plots
+const EXPORT_SIZE: (i32, i32) = (800, 500);
+
+impl Window {
+    pub(super) fn setup_plot(&self) {
+        let imp = self.imp();
+        unsafe {
+            imp.da_plot.set_data("plot", Plot::default());
+        }
+        imp.da_plot.set_draw_func(move |da, ctx, w, h| {
+            if let Some(plot) = unsafe { da.data::<Plot>("plot") } {
+                let plot: &Plot = unsafe { plot.as_ref() };
+                if let Err(e) = draw_plot(plot, ctx, w, h) {
+                    eprintln!("{e}");
+                }
+            }
+        });
+        imp.btn_plot.connect_clicked(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.plot_series()
+        ));
+        for entry in [&imp.txt_plot_series, &imp.txt_plot_x] {
+            entry.connect_activate(clone!(
+                #[weak(rename_to=window)]
+                self,
+                move |_| window.plot_series()
+            ));
+        }
+        imp.btn_plot_scatter.connect_clicked(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.plot_attributes(false)
+        ));
+        imp.btn_plot_hist.connect_clicked(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.plot_attributes(true)
+        ));
+        let click = gtk::GestureClick::new();
+        click.connect_pressed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_, _, x, y| window.plot_select_node(x, y)
+        ));
+        imp.da_plot.add_controller(click);
+        imp.btn_plot_export.connect_clicked(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| window.export_plot_image()
+        ));
+    }
+
+    /// Highlighted nodes, or the selected node if nothing is highlighted
+    fn plot_nodes(&self) -> Vec<usize> {
+        let Some(view) = self.network_view() else {
+            return vec![];
+        };
+        match &view.highlight {
+            Some(h) => {
+                let mut nodes: Vec<usize> = h.nodes.iter().copied().collect();
+                nodes.sort();
+                nodes
+            }
+            None => view.selected.into_iter().collect(),
+        }
+    }
+
+    fn plot_series(&self) {
+        let nodes = self.plot_nodes();
+        if nodes.is_empty() {
+            self.feed_term_text("Select or highlight the nodes to plot\n");
+            return;
+        }
+        self.plot_series_of(&nodes);
+    }
+
+    /// Plot the series of the nodes with the settings of the plot panel
+    fn plot_series_of(&self, nodes: &[usize]) {
+        let imp = self.imp();
+        let tctx = match unsafe { imp.da_network.data::<TaskContext>("tasks_ctx") } {
+            Some(c) => unsafe { &*c.as_ptr() },
+            None => return,
+        };
+        let series = imp.txt_plot_series.text();
+        if series.is_empty() {
+            self.feed_term_text("Enter the name of the series to plot\n");
+            return;
+        }
+        let xseries = imp.txt_plot_x.text();
+        let xseries = Some(xseries.as_str()).filter(|x| !x.is_empty());
+        let kind = match imp.dd_plot_kind.selected() {
+            1 => PlotKind::Scatter,
+            _ => PlotKind::Line,
+        };
+        self.set_plot(Plot::series(&tctx.network, nodes, &series, xseries, kind));
+    }
+
+    fn set_plot(&self, plot: anyhow::Result<Plot>) {
+        let da = &self.imp().da_plot;
+        match plot {
+            Ok(plot) => {
+                unsafe { da.set_data("plot", plot) };
+                da.queue_draw();
+            }
+            Err(e) => self.feed_term_text(&format!("{e}\n")),
+        }
+    }
+
+    /// Do the actions requested by the gui functions in the tasks
+    pub(super) fn process_gui_requests(&self) {
+        let requests = match unsafe { self.imp().da_network.data::<GuiRequests>("gui_requests") } {
+            Some(r) => unsafe { r.as_ref() }.take(),
+            None => return,
+        };
+        for req in requests {
+            let imp = self.imp();
+            match req {
+                GuiRequest::Scatter { x, y, filter } => {
+                    imp.txt_plot_attr_x.set_text(&x);
+                    imp.txt_plot_attr_y.set_text(&y);
+                    imp.txt_plot_filter.set_text(&filter);
+                    self.plot_attributes(false);
+                }
+                GuiRequest::Histogram { attr, filter } => {
+                    imp.txt_plot_attr_x.set_text(&attr);
+                    imp.txt_plot_filter.set_text(&filter);
+                    self.plot_attributes(true);
+                }
+                GuiRequest::Series { series, x, filter } => {
+                    imp.txt_plot_series.set_text(&series);
+                    imp.txt_plot_x.set_text(x.as_deref().unwrap_or_default());
+                    let Some(nodes) = self.filter_nodes(&filter) else {
+                        continue;
+                    };
+                    self.plot_series_of(&nodes);
+                }
+                GuiRequest::ShowTable => {
+                    self.show_table();
+                    continue;
+                }
+                GuiRequest::Export { file, plot: false } => {
+                    self.export_file(&gio::File::for_path(file));
+                    continue;
+                }
+                GuiRequest::Export { file, plot: true } => {
+                    self.save_plot(&file);
+                    continue;
+                }
+            }
+            imp.nb_network.set_current_page(Some(PLOT_PAGE));
+        }
+    }
+
+    fn save_plot(&self, file: &str) {
+        let Some(plot) = (unsafe { self.imp().da_plot.data::<Plot>("plot") }) else {
+            return;
+        };
+        let plot: &Plot = unsafe { plot.as_ref() };
+        let (w, h) = EXPORT_SIZE;
+        if let Err(e) = export_plot(plot, file, w, h) {
+            self.feed_term_text(&format!("{e}\n"));
+        }
+    }
+
+    /// Scatter plot of the two attributes, or the histogram of the
+    /// first, for the nodes passing the filter
+    fn plot_attributes(&self, histogram: bool) {
+        let imp = self.imp();
+        let tctx = match unsafe { imp.da_network.data::<TaskContext>("tasks_ctx") } {
+            Some(c) => unsafe { &*c.as_ptr() },
+            None => return,
+        };
+        let xattr = imp.txt_plot_attr_x.text();
+        let yattr = imp.txt_plot_attr_y.text();
+        if xattr.is_empty() || (!histogram && yattr.is_empty()) {
+            self.feed_term_text("Enter the names of the attributes to plot\n");
+            return;
+        }
+        let Some(nodes) = self.filter_nodes(&imp.txt_plot_filter.text()) else {
+            return;
+        };
+        let plot = if histogram {
+            Plot::histogram(&tctx.network, &nodes, &xattr)
+        } else {
+            Plot::attributes(&tctx.network, &nodes, &xattr, &yattr)
+        };
+        self.set_plot(plot);
+    }
+
+    fn plot_select_node(&self, x: f64, y: f64) {
+        let da = &self.imp().da_plot;
+        let Some(plot) = (unsafe { da.data::<Plot>("plot") }) else {
+            return;
+        };
+        let plot: &Plot = unsafe { plot.as_ref() };
+        if let Some(ind) = node_at(plot, da.width(), da.height(), x, y) {
+            self.select_node(Some(ind));
+            self.select_table_row(Some(ind));
+            self.scroll_to_node(ind);
+        }
+    }
+
+    fn export_plot_image(&self) {
+        let filters = gtk::FileFilter::new();
+        for mime in ["image/png", "image/svg", "application/pdf"] {
+            filters.add_mime_type(mime);
+        }
+        let dialog = gtk::FileDialog::builder()
+            .title("Export Plot")
+            .default_filter(&filters)
+            .initial_file(&gio::File::for_path("plot.pdf"))
+            .accept_label("Save");
+        dialog.build().save(
+            Some(&self.clone()),
+            gio::Cancellable::NONE,
+            clone!(
+                #[weak(rename_to=window)]
+                self,
+                move |file| {
+                    if let Some(path) = file.ok().and_then(|f| f.path()) {
+                        window.save_plot(&path.to_string_lossy());
+                    }
+                }
+            ),
+        );
+    }
+}
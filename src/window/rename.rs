@@ -0,0 +1,340 @@
+use super::{bulk_edit, Window};
+use crate::lint::attr_refs;
+use glib::clone;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{glib, TextBuffer};
+use nadi_core::parser::tokenizer::{self, TaskToken};
+
+/// Change of an attribute name in the buffer, with character offsets
+#[derive(Debug, Clone)]
+struct RenameEdit {
+    start: i32,
+    end: i32,
+}
+
+impl Window {
+    /// Rename an attribute in the current buffer, the attribute at the
+    /// cursor is used by default
+    pub(super) fn rename_attribute(&self) {
+        let buf = self.imp().tv_frame.buffer();
+        let old = variable_at_cursor(&buf).unwrap_or_default();
+
+        let txt_old = gtk::Entry::builder()
+            .text(&old)
+            .placeholder_text("Attribute")
+            .hexpand(true)
+            .build();
+        let txt_new = gtk::Entry::builder()
+            .text(&old)
+            .placeholder_text("New Name")
+            .hexpand(true)
+            .build();
+        let grid = gtk::Grid::builder()
+            .row_spacing(5)
+            .column_spacing(10)
+            .build();
+        grid.attach(&gtk::Label::new(Some("Rename:")), 0, 0, 1, 1);
+        grid.attach(&txt_old, 1, 0, 1, 1);
+        grid.attach(&gtk::Label::new(Some("To:")), 0, 1, 1, 1);
+        grid.attach(&txt_new, 1, 1, 1, 1);
+        let lab_summary = gtk::Label::builder().halign(gtk::Align::Start).build();
+        let preview = gtk::ListBox::new();
+        preview.set_selection_mode(gtk::SelectionMode::None);
+        let scroll = gtk::ScrolledWindow::builder()
+            .child(&preview)
+            .vexpand(true)
+            .build();
+        let btn_cancel = gtk::Button::with_label("Cancel");
+        let btn_rename = gtk::Button::with_label("Rename");
+        btn_rename.add_css_class("suggested-action");
+        let buttons = gtk::Box::new(gtk::Orientation::Horizontal, 10);
+        buttons.set_halign(gtk::Align::End);
+        buttons.append(&btn_cancel);
+        buttons.append(&btn_rename);
+        let content = gtk::Box::builder()
+            .orientation(gtk::Orientation::Vertical)
+            .spacing(10)
+            .margin_start(10)
+            .margin_end(10)
+            .margin_top(10)
+            .margin_bottom(10)
+            .build();
+        content.append(&grid);
+        content.append(&lab_summary);
+        content.append(&scroll);
+        content.append(&buttons);
+        let dialog = gtk::Window::builder()
+            .title("Rename Attribute")
+            .transient_for(self)
+            .modal(true)
+            .default_width(700)
+            .default_height(450)
+            .child(&content)
+            .build();
+
+        let update = clone!(
+            #[weak]
+            buf,
+            #[weak]
+            txt_old,
+            #[weak]
+            txt_new,
+            #[weak]
+            preview,
+            #[weak]
+            lab_summary,
+            #[weak]
+            btn_rename,
+            move || {
+                let (old, new) = (txt_old.text(), txt_new.text());
+                let edits = rename_edits(&buf, &old);
+                show_preview(&buf, &edits, &new, &preview);
+                let valid = is_name(&new) && old != new;
+                btn_rename.set_sensitive(valid && !edits.is_empty());
+                lab_summary.set_text(&if !is_name(&new) {
+                    "The new name should be a valid attribute name".to_string()
+                } else {
+                    format!("{} occurrences of {old}", edits.len())
+                });
+            }
+        );
+        update();
+        let update = std::rc::Rc::new(update);
+        for entry in [&txt_old, &txt_new] {
+            let update = update.clone();
+            entry.connect_changed(move |_| update());
+        }
+        btn_cancel.connect_clicked(clone!(
+            #[weak]
+            dialog,
+            move |_| dialog.close()
+        ));
+        btn_rename.connect_clicked(clone!(
+            #[weak]
+            dialog,
+            #[weak]
+            buf,
+            #[weak]
+            txt_old,
+            #[weak]
+            txt_new,
+            move |_| {
+                let edits = rename_edits(&buf, &txt_old.text());
+                apply_edits(&buf, &edits, &txt_new.text());
+                dialog.close();
+            }
+        ));
+        txt_new.connect_activate(clone!(
+            #[weak]
+            btn_rename,
+            move |_| {
+                if btn_rename.is_sensitive() {
+                    btn_rename.emit_clicked();
+                }
+            }
+        ));
+        dialog.present();
+        txt_new.grab_focus();
+    }
+}
+
+/// Whether the text can be used as an attribute name
+fn is_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || c == '_')
+}
+
+fn variable_at_cursor(buf: &TextBuffer) -> Option<String> {
+    let text = buf.text(&buf.start_iter(), &buf.end_iter(), true);
+    let cursor = buf.iter_at_mark(&buf.get_insert()).offset();
+    let tokens = tokenizer::get_tokens(&text).ok()?;
+    let mut offset = 0;
+    for t in tokens {
+        let len = t.content.chars().count() as i32;
+        if matches!(t.ty, TaskToken::Variable) && offset <= cursor && cursor <= offset + len {
+            return Some(t.content.to_string());
+        }
+        offset += len;
+    }
+    None
+}
+
+fn rename_edits(buf: &TextBuffer, name: &str) -> Vec<RenameEdit> {
+    text_edits(&buf.text(&buf.start_iter(), &buf.end_iter(), true), name)
+}
+
+/// Occurrences of the attribute in the tasks and in the string templates,
+/// the node names and the keyword arguments with the same name are kept
+fn text_edits(text: &str, name: &str) -> Vec<RenameEdit> {
+    if !is_name(name) {
+        return vec![];
+    }
+    let Ok(tokens) = tokenizer::get_tokens(text) else {
+        return vec![];
+    };
+    let len = name.chars().count() as i32;
+    let mut edits: Vec<RenameEdit> = attr_refs(&tokens)
+        .into_iter()
+        .filter(|r| r.name == name)
+        .map(|r| RenameEdit {
+            start: r.offset as i32,
+            end: r.offset as i32 + len,
+        })
+        .collect();
+    let mut offset = 0;
+    for t in &tokens {
+        if let TaskToken::String(_) = t.ty {
+            edits.extend(
+                template_refs(t.content, name)
+                    .into_iter()
+                    .map(|o| RenameEdit {
+                        start: offset + o,
+                        end: offset + o + len,
+                    }),
+            );
+        }
+        offset += t.content.chars().count() as i32;
+    }
+    edits.sort_by_key(|e| e.start);
+    edits
+}
+
+/// Character offsets of the attribute in the templates of the string
+/// like `{name}`, `{_name}`, `{name:f2}` or `{other?name}`
+fn template_refs(text: &str, name: &str) -> Vec<i32> {
+    let chars: Vec<char> = text.chars().collect();
+    let name: Vec<char> = name.chars().collect();
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+    let mut refs = vec![];
+    let mut in_template = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '{' || c == '}' {
+            in_template = c == '{';
+        }
+        i += 1;
+        // names start the template or follow the `?` alternatives
+        if !(in_template && matches!(c, '{' | '?')) {
+            continue;
+        }
+        let start = i;
+        while i < chars.len() && is_word(&chars[i]) {
+            i += 1;
+        }
+        let word = &chars[start..i];
+        if word == name.as_slice() {
+            refs.push(start as i32);
+        } else if word.first() == Some(&'_') && &word[1..] == name.as_slice() {
+            // `_` before the name is part of the template syntax
+            refs.push(start as i32 + 1);
+        }
+    }
+    refs
+}
+
+/// Show the changed lines before and after the rename
+fn show_preview(buf: &TextBuffer, edits: &[RenameEdit], new: &str, list: &gtk::ListBox) {
+    while let Some(row) = list.first_child() {
+        list.remove(&row);
+    }
+    let mut lines: Vec<i32> = edits
+        .iter()
+        .map(|e| buf.iter_at_offset(e.start).line())
+        .collect();
+    lines.dedup();
+    for line in lines {
+        let Some(start) = buf.iter_at_line(line) else {
+            continue;
+        };
+        let mut end = start;
+        if !end.ends_line() {
+            end.forward_to_line_end();
+        }
+        let (s, e) = (start.offset(), end.offset());
+        let text: Vec<char> = buf.text(&start, &end, true).chars().collect();
+        let (mut old, mut changed) = (String::new(), String::new());
+        let mut pos = 0;
+        for ed in edits.iter().filter(|ed| s <= ed.start && ed.end <= e) {
+            let (a, b) = ((ed.start - s) as usize, (ed.end - s) as usize);
+            let before = glib::markup_escape_text(&text[pos..a].iter().collect::<String>());
+            let name = glib::markup_escape_text(&text[a..b].iter().collect::<String>());
+            old.push_str(&format!("{before}<span foreground=\"red\">{name}</span>"));
+            changed.push_str(&format!(
+                "{before}<span foreground=\"green\">{}</span>",
+                glib::markup_escape_text(new)
+            ));
+            pos = b;
+        }
+        let rest = glib::markup_escape_text(&text[pos..].iter().collect::<String>());
+        let label = gtk::Label::new(None);
+        label.set_xalign(0.0);
+        label.set_markup(&format!(
+            "<tt><span foreground=\"gray\">{:>4}</span> - {old}{rest}\n     + {changed}{rest}</tt>",
+            line + 1
+        ));
+        list.append(&label);
+    }
+}
+
+fn apply_edits(buf: &TextBuffer, edits: &[RenameEdit], new: &str) {
+    bulk_edit(buf, || {
+        // from the end so the offsets of the rest stay valid
+        for ed in edits.iter().rev() {
+            let mut start = buf.iter_at_offset(ed.start);
+            let mut end = buf.iter_at_offset(ed.end);
+            buf.delete(&mut start, &mut end);
+            buf.insert(&mut start, new);
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names() {
+        assert!(is_name("area"));
+        assert!(is_name("_tmp2"));
+        assert!(is_name("débit"));
+        assert!(!is_name(""));
+        assert!(!is_name("2area"));
+        assert!(!is_name("area.x"));
+        assert!(!is_name("a b"));
+    }
+
+    #[test]
+    fn template_names() {
+        assert_eq!(template_refs("\"{area}\"", "area"), vec![2]);
+        assert_eq!(template_refs("\"{_area}\"", "area"), vec![3]);
+        assert_eq!(template_refs("\"{area:f2} km\"", "area"), vec![2]);
+        assert_eq!(template_refs("\"{x?area}-{area}\"", "area"), vec![4, 11]);
+    }
+
+    fn starts(text: &str, name: &str) -> Vec<i32> {
+        text_edits(text, name).iter().map(|e| e.start).collect()
+    }
+
+    #[test]
+    fn rename_attributes() {
+        let text = "node.area = 1\nnode.y = area * 2\nnode.z = \"{area:f2}\"\n";
+        assert_eq!(starts(text, "area"), vec![5, 23, 43]);
+        assert!(starts(text, "1area").is_empty());
+    }
+
+    #[test]
+    fn keep_names_and_kwargs() {
+        let text = "node[area].x = f(area=area)\nnode[a -> area].y = 1\n";
+        assert_eq!(starts(text, "area"), vec![22]);
+    }
+
+    #[test]
+    fn template_other_names() {
+        assert!(template_refs("\"area {areas} {x.area}\"", "area").is_empty());
+        assert_eq!(template_refs("\"{_area}\"", "_area"), vec![2]);
+        assert!(template_refs("\"}area{\"", "area").is_empty());
+    }
+}
@@ -0,0 +1,267 @@
+use super::Window;
+use crate::figures::with_figure;
+use crate::styles::split_styles;
+use glib::{clone, BoxedAnyObject};
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gdk, gio, glib};
+use nadi_core::prelude::*;
+use nadi_core::table::Table;
+use nadi_core::tasks::TaskContext;
+use std::cmp::Ordering;
+
+/// Page of the table in the network notebook
+const TABLE_PAGE: u32 = 1;
+
+/// Row of the interactive table
+struct TableRow {
+    index: usize,
+    cells: Vec<String>,
+}
+
+impl Window {
+    pub(super) fn setup_table(&self) {
+        let imp = self.imp();
+        let cv = &imp.cv_table;
+        let store = gio::ListStore::new::<BoxedAnyObject>();
+        let sorted = gtk::SortListModel::new(Some(store.clone()), cv.sorter());
+        let selection = gtk::SingleSelection::new(Some(sorted));
+        selection.set_autoselect(false);
+        selection.set_can_unselect(true);
+        selection.connect_selected_item_notify(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |sel| {
+                // the rows selected from the network view are already shown
+                if unsafe { sel.data::<bool>("syncing") }.is_some() {
+                    return;
+                }
+                if let Some(obj) = sel.selected_item().and_downcast::<BoxedAnyObject>() {
+                    let index = obj.borrow::<TableRow>().index;
+                    window.select_node(Some(index));
+                    window.scroll_to_node(index);
+                }
+            }
+        ));
+        cv.set_model(Some(&selection));
+        unsafe {
+            cv.set_data("table_store", store);
+            cv.set_data("table_headers", Vec::<String>::new());
+            cv.set_data("table_column", 0usize);
+        }
+
+        let keys = gtk::EventControllerKey::new();
+        keys.connect_key_pressed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            #[upgrade_or]
+            glib::Propagation::Proceed,
+            move |_, key, _, state| {
+                if key == gdk::Key::c && state.contains(gdk::ModifierType::CONTROL_MASK) {
+                    window.copy_table_cell();
+                    return glib::Propagation::Stop;
+                }
+                glib::Propagation::Proceed
+            }
+        ));
+        cv.add_controller(keys);
+
+        imp.nb_network.connect_switch_page(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_, _, page| {
+                if page == TABLE_PAGE {
+                    window.refresh_table();
+                }
+            }
+        ));
+    }
+
+    pub(super) fn show_table(&self) {
+        self.imp().nb_network.set_current_page(Some(TABLE_PAGE));
+    }
+
+    fn table_selection(&self) -> Option<gtk::SingleSelection> {
+        self.imp()
+            .cv_table
+            .model()
+            .and_downcast::<gtk::SingleSelection>()
+    }
+
+    /// Fill the table from the `drawtable` attribute of the network,
+    /// or just the node names if there isn't one
+    pub(super) fn refresh_table(&self) {
+        if self.imp().nb_network.current_page() != Some(TABLE_PAGE) {
+            return;
+        }
+        let net = match unsafe { self.imp().da_network.data::<TaskContext>("tasks_ctx") } {
+            Some(c) => &unsafe { c.as_ref() }.network,
+            None => return,
+        };
+        let figure = self.network_view().and_then(|v| v.figure.clone());
+        let table = with_figure(net, figure.as_deref(), |attrs| {
+            match attrs
+                .attr("drawtable")
+                .and_then(|t| Table::from_attr(&split_styles(t).0))
+            {
+                Some(t) => t
+                    .render_contents(net, false)
+                    .map(|c| (t.columns.iter().map(|c| c.header.to_string()).collect(), c)),
+                None => Ok((
+                    vec!["Name".to_string()],
+                    net.nodes()
+                        .map(|n| vec![n.lock().name().to_string()])
+                        .collect(),
+                )),
+            }
+        });
+        let (headers, contents): (Vec<String>, Vec<Vec<String>>) = match table {
+            Ok(t) => t,
+            Err(e) => {
+                self.feed_term_text(&format!("{e}\n"));
+                return;
+            }
+        };
+        let cv = &self.imp().cv_table;
+        let old_headers = unsafe { cv.data::<Vec<String>>("table_headers") }
+            .map(|h| unsafe { h.as_ref() }.clone())
+            .unwrap_or_default();
+        // rebuilding the columns resets their width and sorting, so
+        // only do it when they are different
+        if old_headers != headers {
+            while let Some(col) = cv.columns().item(0).and_downcast::<gtk::ColumnViewColumn>() {
+                cv.remove_column(&col);
+            }
+            for (i, head) in headers.iter().enumerate() {
+                cv.append_column(&self.table_column(i, head));
+            }
+            unsafe { cv.set_data("table_headers", headers) };
+        }
+        let Some(store) = (unsafe { cv.data::<gio::ListStore>("table_store") }) else {
+            return;
+        };
+        let store: &gio::ListStore = unsafe { store.as_ref() };
+        // only the nodes shown in the figure and passing the filter
+        let view = self.network_view();
+        let rows: Vec<BoxedAnyObject> = contents
+            .into_iter()
+            .enumerate()
+            .filter(|(index, _)| view.as_ref().map(|v| v.is_visible(*index)).unwrap_or(true))
+            .map(|(index, cells)| BoxedAnyObject::new(TableRow { index, cells }))
+            .collect();
+        store.splice(0, store.n_items(), &rows);
+        let selected = self.network_view().and_then(|v| v.selected);
+        self.select_table_row(selected);
+    }
+
+    fn table_column(&self, col: usize, header: &str) -> gtk::ColumnViewColumn {
+        let factory = gtk::SignalListItemFactory::new();
+        factory.connect_setup(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_, item| {
+                let item = item
+                    .downcast_ref::<gtk::ListItem>()
+                    .expect("Needs to be ListItem");
+                let label = gtk::Label::new(None);
+                label.set_halign(gtk::Align::Start);
+                // remember the column for copying the cell
+                let click = gtk::GestureClick::new();
+                click.connect_pressed(clone!(
+                    #[weak]
+                    window,
+                    move |_, _, _, _| unsafe {
+                        window.imp().cv_table.set_data("table_column", col);
+                    }
+                ));
+                label.add_controller(click);
+                item.set_child(Some(&label));
+            }
+        ));
+        factory.connect_bind(move |_, item| {
+            let item = item
+                .downcast_ref::<gtk::ListItem>()
+                .expect("Needs to be ListItem");
+            let (Some(label), Some(obj)) = (
+                item.child().and_downcast::<gtk::Label>(),
+                item.item().and_downcast::<BoxedAnyObject>(),
+            ) else {
+                return;
+            };
+            let row = obj.borrow::<TableRow>();
+            label.set_text(row.cells.get(col).map(|c| c.as_str()).unwrap_or_default());
+        });
+        let sorter = gtk::CustomSorter::new(move |a, b| {
+            let (Some(a), Some(b)) = (
+                a.downcast_ref::<BoxedAnyObject>(),
+                b.downcast_ref::<BoxedAnyObject>(),
+            ) else {
+                return gtk::Ordering::Equal;
+            };
+            let (a, b) = (a.borrow::<TableRow>(), b.borrow::<TableRow>());
+            compare_cells(
+                a.cells.get(col).map(|c| c.as_str()).unwrap_or_default(),
+                b.cells.get(col).map(|c| c.as_str()).unwrap_or_default(),
+            )
+            .into()
+        });
+        let column = gtk::ColumnViewColumn::new(Some(header), Some(factory));
+        column.set_resizable(true);
+        column.set_sorter(Some(&sorter));
+        column
+    }
+
+    /// Select the row of the node in the table without scrolling the
+    /// network view
+    pub(super) fn select_table_row(&self, index: Option<usize>) {
+        let Some(selection) = self.table_selection() else {
+            return;
+        };
+        let pos = index.and_then(|ind| {
+            (0..selection.n_items()).find(|&i| {
+                selection
+                    .item(i)
+                    .and_downcast::<BoxedAnyObject>()
+                    .map(|o| o.borrow::<TableRow>().index == ind)
+                    .unwrap_or(false)
+            })
+        });
+        unsafe { selection.set_data("syncing", true) };
+        match pos {
+            Some(p) if selection.selected() != p => {
+                selection.set_selected(p);
+                self.imp()
+                    .cv_table
+                    .scroll_to(p, None, gtk::ListScrollFlags::NONE, None);
+            }
+            Some(_) => (),
+            None => selection.unselect_all(),
+        }
+        unsafe { selection.steal_data::<bool>("syncing") };
+    }
+
+    fn copy_table_cell(&self) {
+        let Some(selection) = self.table_selection() else {
+            return;
+        };
+        let Some(obj) = selection.selected_item().and_downcast::<BoxedAnyObject>() else {
+            return;
+        };
+        let cv = &self.imp().cv_table;
+        let col = unsafe { cv.data::<usize>("table_column") }
+            .map(|c| *unsafe { c.as_ref() })
+            .unwrap_or_default();
+        let row = obj.borrow::<TableRow>();
+        if let Some(cell) = row.cells.get(col) {
+            cv.clipboard().set_text(cell);
+        }
+    }
+}
+
+/// Compare the cells as numbers if both of them are numbers
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.total_cmp(&y),
+        _ => a.cmp(b),
+    }
+}
@@ -0,0 +1,382 @@
+use super::Window;
+use glib::clone;
+use gtk::prelude::*;
+use gtk::subclass::prelude::*;
+use gtk::{gio, glib, TextBuffer};
+use std::path::Path;
+
+/// Window title shown after the file name
+const TITLE: &str = "NADI";
+
+/// Answer to the prompt about the unsaved changes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Unsaved {
+    Save,
+    Discard,
+    Cancel,
+}
+
+/// Tasks file opened in a tab of the editor, the text view shows the
+/// buffer of the current tab
+struct EditorTab {
+    buffer: TextBuffer,
+    path: String,
+    page: gtk::Box,
+    label: gtk::Label,
+    /// run the file when the network is reloaded from another tab
+    run: gtk::CheckButton,
+}
+
+impl EditorTab {
+    fn name(&self) -> String {
+        Path::new(&self.path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or("Untitled".to_string())
+    }
+
+    /// File name with a `*` if it has unsaved changes
+    fn title(&self) -> String {
+        let dirty = if self.buffer.is_modified() { "*" } else { "" };
+        format!("{dirty}{}", self.name())
+    }
+
+    fn update_label(&self) {
+        self.label.set_text(&self.title());
+        self.label.set_tooltip_text(Some(&self.path));
+    }
+
+    fn save(&self) -> anyhow::Result<()> {
+        let txt = self
+            .buffer
+            .text(&self.buffer.start_iter(), &self.buffer.end_iter(), true);
+        std::fs::write(&self.path, txt.as_bytes())?;
+        self.buffer.set_modified(false);
+        Ok(())
+    }
+}
+
+impl Window {
+    pub(super) fn setup_tabs(&self) {
+        let imp = self.imp();
+        unsafe {
+            imp.nb_editor
+                .set_data("editor_tabs", Vec::<EditorTab>::new());
+        }
+        let btn = gtk::Button::from_icon_name("tab-new-symbolic");
+        btn.set_has_frame(false);
+        btn.set_tooltip_text(Some("New Tab"));
+        btn.connect_clicked(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_| {
+                window.add_tab(None, "");
+            }
+        ));
+        imp.nb_editor.set_action_widget(&btn, gtk::PackType::End);
+        imp.nb_editor.connect_switch_page(clone!(
+            #[weak(rename_to=window)]
+            self,
+            move |_, page, _| window.show_tab(page)
+        ));
+        // the buffer from the template is the first tab
+        self.add_tab(Some(imp.tv_frame.buffer()), "");
+    }
+
+    fn editor_tabs(&self) -> Option<&mut Vec<EditorTab>> {
+        unsafe {
+            self.imp()
+                .nb_editor
+                .data::<Vec<EditorTab>>("editor_tabs")
+                .map(|t| &mut *t.as_ptr())
+        }
+    }
+
+    fn tab_of_page(&self, page: &gtk::Widget) -> Option<&mut EditorTab> {
+        self.editor_tabs()?
+            .iter_mut()
+            .find(|t| t.page.upcast_ref::<gtk::Widget>() == page)
+    }
+
+    fn current_tab(&self) -> Option<&mut EditorTab> {
+        let nb = &self.imp().nb_editor;
+        let page = nb.nth_page(nb.current_page())?;
+        self.tab_of_page(&page)
+    }
+
+    /// Add a tab with the buffer (or a new one) and show it
+    pub(super) fn add_tab(&self, buffer: Option<TextBuffer>, path: &str) -> TextBuffer {
+        let imp = self.imp();
+        let buffer = buffer.unwrap_or_else(|| {
+            // share the tags used for the highlighting
+            TextBuffer::new(Some(&imp.tv_frame.buffer().tag_table()))
+        });
+        self.setup_buffer(&buffer);
+
+        let label = gtk::Label::new(None);
+        let run = gtk::CheckButton::new();
+        run.set_tooltip_text(Some("Also run this file when reloading from another tab"));
+        let close = gtk::Button::from_icon_name("window-close-symbolic");
+        close.set_has_frame(false);
+        close.set_tooltip_text(Some("Close the Tab"));
+        let tab_box = gtk::Box::new(gtk::Orientation::Horizontal, 4);
+        tab_box.append(&run);
+        tab_box.append(&label);
+        tab_box.append(&close);
+        let page = gtk::Box::new(gtk::Orientation::Vertical, 0);
+        close.connect_clicked(clone!(
+            #[weak(rename_to=window)]
+            self,
+            #[weak]
+            page,
+            move |_| window.confirm_close_tab(page.upcast_ref())
+        ));
+        buffer.connect_modified_changed(clone!(
+            #[weak(rename_to=window)]
+            self,
+            #[weak]
+            page,
+            move |_| {
+                if let Some(tab) = window.tab_of_page(page.upcast_ref()) {
+                    tab.update_label();
+                }
+                window.update_title();
+            }
+        ));
+
+        let tab = EditorTab {
+            buffer: buffer.clone(),
+            path: path.to_string(),
+            page: page.clone(),
+            label,
+            run,
+        };
+        tab.update_label();
+        if let Some(tabs) = self.editor_tabs() {
+            tabs.push(tab);
+        }
+        let nb = &imp.nb_editor;
+        let n = nb.append_page(&page, Some(&tab_box));
+        nb.set_tab_reorderable(&page, true);
+        nb.set_current_page(Some(n));
+        buffer
+    }
+
+    /// Buffer to open the file in: the tab that already has it, the
+    /// current tab if it is empty, or a new tab
+    pub(super) fn open_tab(&self, path: &str) -> TextBuffer {
+        let nb = &self.imp().nb_editor;
+        if let Some(tab) = self
+            .editor_tabs()
+            .and_then(|tabs| tabs.iter().find(|t| t.path == path))
+        {
+            nb.set_current_page(nb.page_num(&tab.page));
+            return tab.buffer.clone();
+        }
+        match self.current_tab() {
+            Some(tab)
+                if tab.path.is_empty()
+                    && !tab.buffer.is_modified()
+                    && tab.buffer.char_count() == 0 =>
+            {
+                let buf = tab.buffer.clone();
+                self.set_tab_path(path);
+                buf
+            }
+            _ => self.add_tab(None, path),
+        }
+    }
+
+    fn show_tab(&self, page: &gtk::Widget) {
+        let Some(tab) = self.tab_of_page(page) else {
+            return;
+        };
+        let imp = self.imp();
+        imp.tv_frame.set_buffer(Some(&tab.buffer));
+        imp.txt_browse.set_text(&tab.path);
+        self.update_title();
+        self.hide_completion();
+        self.refresh_signature();
+        self.check_tasks();
+        self.refresh_outline();
+        self.refresh_find();
+        imp.tv_frame
+            .scroll_to_mark(&tab.buffer.get_insert(), 0.1, false, 0.0, 0.0);
+    }
+
+    pub(super) fn close_tab(&self, page: &gtk::Widget) {
+        let nb = &self.imp().nb_editor;
+        if nb.n_pages() <= 1 {
+            // keep an empty tab to type in
+            if let Some(tab) = self.tab_of_page(page) {
+                tab.buffer.set_text("");
+                tab.buffer.set_modified(false);
+            }
+            self.set_tab_path("");
+            return;
+        }
+        if let Some(tabs) = self.editor_tabs() {
+            tabs.retain(|t| t.page.upcast_ref::<gtk::Widget>() != page);
+        }
+        nb.remove_page(nb.page_num(page));
+    }
+
+    /// Change the file path of the current tab
+    pub(super) fn set_tab_path(&self, path: &str) {
+        self.imp().txt_browse.set_text(path);
+        if let Some(tab) = self.current_tab() {
+            tab.path = path.to_string();
+            tab.update_label();
+        }
+        self.update_title();
+    }
+
+    fn update_title(&self) {
+        let title = match self.current_tab() {
+            Some(tab) => format!("{} - {TITLE}", tab.title()),
+            None => TITLE.to_string(),
+        };
+        self.set_title(Some(&title));
+    }
+
+    /// Tab with the file open that has unsaved changes
+    pub(super) fn modified_tab(&self, path: &str) -> Option<TextBuffer> {
+        self.editor_tabs()?
+            .iter()
+            .find(|t| t.path == path && t.buffer.is_modified())
+            .map(|t| t.buffer.clone())
+    }
+
+    /// Ask whether to save the changes, `detail` says what happens to them
+    pub(super) fn ask_unsaved(
+        &self,
+        detail: &str,
+        callback: impl FnOnce(&Window, Unsaved) + 'static,
+    ) {
+        let dialog = gtk::AlertDialog::builder()
+            .modal(true)
+            .message("Save the changes?")
+            .detail(detail)
+            .buttons(["Cancel", "Discard", "Save"])
+            .cancel_button(0)
+            .default_button(2)
+            .build();
+        dialog.choose(
+            Some(self),
+            gio::Cancellable::NONE,
+            clone!(
+                #[weak(rename_to=window)]
+                self,
+                move |res| {
+                    let choice = match res {
+                        Ok(2) => Unsaved::Save,
+                        Ok(1) => Unsaved::Discard,
+                        _ => Unsaved::Cancel,
+                    };
+                    callback(&window, choice)
+                }
+            ),
+        );
+    }
+
+    /// Save the tab, untitled tabs are shown and asked for a file name;
+    /// returns whether it was saved
+    fn save_tab(&self, page: &gtk::Widget) -> bool {
+        let Some(tab) = self.tab_of_page(page) else {
+            return false;
+        };
+        if tab.path.is_empty() {
+            let nb = &self.imp().nb_editor;
+            nb.set_current_page(nb.page_num(page));
+            self.save_file_as();
+            return false;
+        }
+        match tab.save() {
+            Ok(()) => true,
+            Err(e) => {
+                self.feed_term_text(&format!("Error saving {}: {e}\n", tab.path));
+                false
+            }
+        }
+    }
+
+    fn confirm_close_tab(&self, page: &gtk::Widget) {
+        let Some(tab) = self.tab_of_page(page) else {
+            return;
+        };
+        if !tab.buffer.is_modified() {
+            self.close_tab(page);
+            return;
+        }
+        let detail = format!(
+            "{} has unsaved changes.",
+            tab.title().trim_start_matches('*')
+        );
+        let page = page.clone();
+        self.ask_unsaved(&detail, move |window, choice| match choice {
+            Unsaved::Save => {
+                if window.save_tab(&page) {
+                    window.close_tab(&page);
+                }
+            }
+            Unsaved::Discard => window.close_tab(&page),
+            Unsaved::Cancel => (),
+        });
+    }
+
+    /// Whether the window can close now, otherwise it asks about the
+    /// unsaved tabs and closes the window afterwards
+    pub(super) fn confirm_close(&self) -> bool {
+        let confirmed = unsafe { self.data::<bool>("close_confirmed") }
+            .map(|c| *unsafe { c.as_ref() })
+            .unwrap_or(false);
+        let unsaved: Vec<(gtk::Widget, String)> = self
+            .editor_tabs()
+            .map(|tabs| {
+                tabs.iter()
+                    .filter(|t| t.buffer.is_modified())
+                    .map(|t| (t.page.clone().upcast(), t.title()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if confirmed || unsaved.is_empty() {
+            return true;
+        }
+        let names: Vec<&str> = unsaved
+            .iter()
+            .map(|(_, n)| n.trim_start_matches('*'))
+            .collect();
+        let detail = format!("Unsaved changes in: {}", names.join(", "));
+        self.ask_unsaved(&detail, move |window, choice| {
+            match choice {
+                Unsaved::Save => {
+                    // stop at the first one that couldn't be saved
+                    if !unsaved.iter().all(|(p, _)| window.save_tab(p)) {
+                        return;
+                    }
+                }
+                Unsaved::Discard => (),
+                Unsaved::Cancel => return,
+            }
+            unsafe { window.set_data("close_confirmed", true) };
+            window.close();
+        });
+        false
+    }
+
+    /// Names and buffers of the tabs to run when reloading the network,
+    /// the current tab and the ones marked to run, in the order of the
+    /// tabs
+    pub(super) fn tabs_to_run(&self) -> Vec<(String, TextBuffer)> {
+        let nb = &self.imp().nb_editor;
+        let current = nb.current_page();
+        (0..nb.n_pages())
+            .filter_map(|i| Some((i, nb.nth_page(Some(i))?)))
+            .filter_map(|(i, p)| {
+                self.tab_of_page(&p)
+                    .filter(|t| t.run.is_active() || Some(i) == current)
+                    .map(|t| (t.name(), t.buffer.clone()))
+            })
+            .collect()
+    }
+}